@@ -8,7 +8,9 @@ use std::{
 };
 
 use eframe::egui::{self};
-use password_crack::{CharsetChoice, Cracker, Strategy};
+use password_crack::{
+    CharsetChoice, Cracker, DictEncoding, Event, EventReceiver, Outcome, Strategy,
+};
 use time::OffsetDateTime;
 
 use crate::{font::setup_custom_fonts, ui::progress_bar, Mode};
@@ -20,19 +22,22 @@ pub struct App {
     pub mode: Mode,
     pub workers_count: usize,
     pub selected_charset: [(CharsetChoice, bool); 4],
-    pub password_count: Result<usize, String>,
+    pub password_count: Result<Option<usize>, String>,
     pub tested_count: usize,
     pub progress: f32,
     strategy: Option<Strategy>,
     running: bool,
-    pub find_result: Option<Option<String>>,
-    password_receiver: Option<Receiver<Option<Option<String>>>>,
+    pub find_result: Option<Result<Outcome, String>>,
+    password_receiver: Option<Receiver<Result<Outcome, String>>>,
     pub start_time: Option<OffsetDateTime>,
     pub current_time: Option<OffsetDateTime>,
     pub min_pasword_length: usize, // timer:time
     pub max_pasword_length: usize,
     pub custom_charsets: String,
     pub crack: Option<Cracker>,
+    events: Option<EventReceiver>,
+    pub target_info: Option<String>,
+    dictionary_progress: Option<(u64, u64)>,
 }
 impl App {
     fn reset(self: &mut Self) {
@@ -40,6 +45,8 @@ impl App {
         self.current_time = None;
         self.tested_count = 0;
         self.find_result = None;
+        self.target_info = None;
+        self.dictionary_progress = None;
     }
 }
 impl eframe::App for App {
@@ -49,7 +56,11 @@ impl eframe::App for App {
             Mode::PasswordDictionary => {
                 if self.dictionary_path.is_some() {
                     let path = Path::new(self.dictionary_path.as_ref().unwrap());
-                    let strategy = Strategy::PasswordFile(path.to_path_buf());
+                    let strategy = Strategy::PasswordFile {
+                        path: path.to_path_buf(),
+                        encoding: DictEncoding::default(),
+                        rules: vec![],
+                    };
 
                     self.strategy = Some(strategy);
                 }
@@ -66,6 +77,8 @@ impl eframe::App for App {
                         charsets,
                         min_password_len: self.min_pasword_length,
                         max_password_len: self.max_pasword_length,
+                        skip: 0,
+                        limit: None,
                     };
 
                     self.strategy = Some(strategy);
@@ -83,6 +96,8 @@ impl eframe::App for App {
                         charsets,
                         min_password_len: self.min_pasword_length,
                         max_password_len: self.max_pasword_length,
+                        skip: 0,
+                        limit: None,
                     };
                     self.strategy = Some(strategy);
                 }
@@ -128,7 +143,9 @@ impl eframe::App for App {
 
                         let work_count = self.workers_count;
                         self.reset();
-                        let crack = Cracker::new(file.unwrap(), work_count, strategy.unwrap());
+                        let mut crack =
+                            Cracker::new(file.unwrap(), work_count, strategy.unwrap());
+                        self.events = Some(crack.events());
 
                         self.password_count = crack
                             .count()
@@ -136,26 +153,51 @@ impl eframe::App for App {
                         self.crack = Some(crack.clone());
                         let crack1 = Arc::new(crack);
                         thread::spawn(move || match crack1.start() {
-                            Ok(Some(password)) => {
-                                println!("password: {}", password);
-                                send_password_find.send(Some(Some(password))).unwrap();
-                            }
-                            Ok(None) => {
-                                send_password_find.send(Some(None)).unwrap();
+                            Ok(outcome) => {
+                                send_password_find.send(Ok(outcome)).unwrap();
                             }
                             Err(e) => {
-                                println!("err: {:?}", e);
-                                send_password_find.send(None).unwrap();
+                                send_password_find.send(Err(e.to_string())).unwrap();
                             }
                         });
                     }
                 });
+                ui.add_enabled_ui(self.running, |ui| {
+                    if let Some(crack) = self.crack.as_ref() {
+                        let paused = crack.is_paused();
+                        if ui.button(if paused { "继续" } else { "暂停" }).clicked() {
+                            if paused {
+                                crack.resume();
+                            } else {
+                                crack.pause();
+                            }
+                        }
+                    }
+                    if ui.button("停止").clicked() {
+                        if let Some(crack) = self.crack.as_ref() {
+                            crack.cancel();
+                        }
+                    }
+                });
 
                 progress_bar(self, ui);
 
+                if let Some(events) = self.events.as_ref() {
+                    for event in events.try_iter() {
+                        match event {
+                            Event::TargetInfo { description, .. } => {
+                                self.target_info = description;
+                            }
+                            Event::DictionaryProgress { read, size } => {
+                                self.dictionary_progress = Some((read, size));
+                            }
+                            _ => (),
+                        }
+                    }
+                }
                 if self.password_receiver.is_some() {
                     if let Ok(r) = self.password_receiver.as_ref().unwrap().try_recv() {
-                        self.find_result = r;
+                        self.find_result = Some(r);
                         self.running = false;
                     }
                 }
@@ -165,8 +207,12 @@ impl eframe::App for App {
                     }
                     self.tested_count = self.crack.as_ref().unwrap().tested_count() as usize;
 
-                    self.progress =
-                        self.tested_count as f32 / *self.password_count.as_ref().unwrap() as f32;
+                    self.progress = match (self.password_count.as_ref(), self.dictionary_progress) {
+                        (Ok(Some(count)), _) => self.tested_count as f32 / *count as f32,
+                        // compressed dictionary, progress in bytes
+                        (_, Some((read, size))) => read as f32 / size as f32,
+                        _ => 0.0,
+                    };
                 }
             });
         });
@@ -185,7 +231,7 @@ impl App {
                 (CharsetChoice::Upper, false),
                 (CharsetChoice::Special, false),
             ],
-            password_count: Ok(0),
+            password_count: Ok(Some(0)),
             progress: 0.0,
             strategy: None,
             running: false,
@@ -199,6 +245,9 @@ impl App {
             max_pasword_length: 8,
             custom_charsets: String::new(),
             crack: None,
+            events: None,
+            target_info: None,
+            dictionary_progress: None,
         }
     }
 }
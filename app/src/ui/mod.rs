@@ -3,6 +3,8 @@ use eframe::{
     emath::Align,
 };
 
+use password_crack::Outcome;
+
 use crate::{app::App, Mode};
 
 pub fn file_selector(app: &mut App, ui: &mut Ui) {
@@ -78,11 +80,15 @@ pub fn password_length(app: &mut App, ui: &mut Ui) {
 }
 
 pub fn progress_bar(app: &mut App, ui: &mut Ui) {
+    if let Some(target_info) = app.target_info.as_ref() {
+        ui.label(target_info);
+    }
     let progressbar = eframe::egui::ProgressBar::new(app.progress).show_percentage();
     ui.add(progressbar);
     ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
         let msg = match app.password_count.as_ref() {
-            Ok(count) => count.to_string(),
+            Ok(Some(count)) => count.to_string(),
+            Ok(None) => "?".to_string(),
             Err(err) => err.to_owned(),
         };
         ui.label(format!("{}/{}", app.tested_count, msg));
@@ -99,12 +105,18 @@ pub fn progress_bar(app: &mut App, ui: &mut Ui) {
     });
     if app.find_result.is_some() {
         match app.find_result.as_ref().unwrap() {
-            Some(password) => {
+            Ok(Outcome::Found { password, .. }) => {
                 ui.label(format!("已查找密码: {}", password));
             }
-            None => {
+            Ok(Outcome::NotFound) => {
                 ui.label("未查找密码！");
             }
+            Ok(Outcome::Cancelled { tested }) => {
+                ui.label(format!("已停止，已测试 {} 个密码", tested));
+            }
+            Err(err) => {
+                ui.label(format!("错误: {}", err));
+            }
         }
     }
     ui.end_row();
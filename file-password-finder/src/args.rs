@@ -1,11 +1,22 @@
 use clap::{crate_authors, crate_description, crate_name, crate_version, value_parser};
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use itertools::Itertools;
-use password_crack::CharsetChoice;
-use std::path::Path;
+use password_crack::{
+    CharsetChoice, DateLayout, Dates, DictEncoding, Fragments, Mask, PasswordEncoding, Rules,
+    Template, WordCase,
+};
+use std::path::{Path, PathBuf};
 
 use crate::cli_error::CLIError::{self, *};
 
+/// Charsets of the placeholders `?1` to `?4` of a mask.
+const CUSTOM_CHARSETS: [&str; 4] = [
+    "customCharset1",
+    "customCharset2",
+    "customCharset3",
+    "customCharset4",
+];
+
 fn command() -> clap::Command {
     Command::new(crate_name!())
         .version(crate_version!())
@@ -17,7 +28,7 @@ fn command() -> clap::Command {
                 .long("inputFile")
                 .short('i')
                 .num_args(1)
-                .required(true),
+                .required_unless_present("restore"),
         )
         .arg(
             Arg::new("workers")
@@ -30,10 +41,134 @@ fn command() -> clap::Command {
         )
         .arg(
             Arg::new("passwordDictionary")
-                .help("path to a password dictionary file")
+                .help("path to a password dictionary file or directory, can be repeated")
                 .long("passwordDictionary")
                 .short('p')
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .required(false),
+        )
+        .arg(
+            Arg::new("stdin")
+                .help("read the passwords to test from the standard input, one per line")
+                .long("stdin")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["passwordDictionary", "restore"]),
+        )
+        .arg(
+            Arg::new("dedup")
+                .help("skip the dictionary lines already tested")
+                .long("dedup")
+                .action(ArgAction::SetTrue)
+                .requires("passwordDictionary"),
+        )
+        .arg(
+            Arg::new("rules")
+                .help("hashcat rule file applied to every dictionary line, can be repeated")
+                .long("rules")
+                .short('r')
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("rightDictionary")
+                .help("path to a second password dictionary, every line of the password dictionary is joined to every line of this one")
+                .long("rightDictionary")
+                .num_args(1)
+                .requires("passwordDictionary")
+                .conflicts_with_all(["mask", "rules", "dedup"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("fragments")
+                .help("known pieces of the password, every ordered combination of distinct pieces is tested")
+                .long("fragments")
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .conflicts_with_all(["passwordDictionary", "stdin", "mask", "rules", "restore", "skip", "startFrom", "limit"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("minFragments")
+                .value_parser(value_parser!(usize))
+                .help("minimum number of pieces combined with 'fragments'")
+                .long("minFragments")
+                .num_args(1)
+                .default_value("1")
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("maxFragments")
+                .value_parser(value_parser!(usize))
+                .help("maximum number of pieces combined with 'fragments' [default: all of them]")
+                .long("maxFragments")
+                .num_args(1)
+                .requires("fragments")
+                .required(false),
+        )
+        .arg(
+            Arg::new("typos")
+                .help("password which should work but does not, its variants with typos are tested, fewest typos first, with the charsets for insertions and substitutions")
+                .long("typos")
+                .num_args(1)
+                .conflicts_with_all(["passwordDictionary", "stdin", "mask", "rules", "fragments", "restore", "skip", "startFrom", "limit"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("maxEdits")
+                .value_parser(value_parser!(usize))
+                .help("maximum number of typos in the variants of 'typos'")
+                .long("maxEdits")
+                .num_args(1)
+                .default_value("1")
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("separator")
+                .help("put between the two words joined with 'rightDictionary', or between the 'fragments' where it can be repeated")
+                .long("separator")
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("cases")
+                .help(format!(
+                    "cases of the words joined with 'rightDictionary' or of the 'fragments': {}",
+                    WordCase::to_string()
+                ))
+                .long("cases")
+                .value_delimiter(',')
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("dictEncoding")
+                .help(format!(
+                    "encoding of the password dictionary or standard input: {}",
+                    DictEncoding::to_string()
+                ))
+                .long("dictEncoding")
                 .num_args(1)
+                .default_value("utf-8")
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("encodings")
+                .help(format!(
+                    "encodings to try for every password, in order: {} (cp437 and cp936 only for zip and pdf)",
+                    PasswordEncoding::to_string()
+                ))
+                .long("encodings")
+                .value_delimiter(',')
+                .default_value("utf-8")
+                .conflicts_with("restore")
                 .required(false),
         )
         .arg(
@@ -46,6 +181,7 @@ fn command() -> clap::Command {
                 .short('c')
                 .value_delimiter(',')
                 .default_value("number")
+                .conflicts_with("restore")
                 .required(false),
         )
         .arg(
@@ -53,8 +189,100 @@ fn command() -> clap::Command {
                 .help("charset to use to generate password")
                 .long("customCharset")
                 .value_delimiter(',')
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("mask")
+                .help("hashcat-style mask of the passwords to generate, appended to every dictionary line with a password dictionary, e.g. Admin?d?d?d?d: ?l lower, ?u upper, ?d number, ?s special, ?a all, ?1 to ?4 custom charsets, ?? for '?'")
+                .long("mask")
+                .short('m')
+                .num_args(1)
+                .conflicts_with_all(["stdin", "restore", "skip", "startFrom", "limit", "rules", "dedup"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("template")
+                .help("what is known of the password, e.g. zhang{1985..1995}{?d?d}! or Pa{s,$}{s,$}w0rd: {a,b} any alternative, {1..12} any number, placeholders like in 'mask', \\ before a character to keep it as it is")
+                .long("template")
+                .num_args(1)
+                .conflicts_with_all(["passwordDictionary", "stdin", "mask", "rules", "fragments", "typos", "restore", "skip", "startFrom", "limit"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("dates")
+                .help("test the calendar dates of these years, e.g. 1980..2000 or 1990")
+                .long("dates")
+                .num_args(1)
+                .conflicts_with_all(["passwordDictionary", "stdin", "mask", "rules", "fragments", "typos", "template", "restore", "skip", "startFrom", "limit"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("dateLayouts")
+                .help(format!(
+                    "layouts of the 'dates': {} [default: all of them]",
+                    DateLayout::to_string()
+                ))
+                .long("dateLayouts")
+                .value_delimiter(',')
+                .requires("dates")
+                .required(false),
+        )
+        .arg(
+            Arg::new("unpadded")
+                .help("also test the 'dates' without the leading zeros of their month and day")
+                .long("unpadded")
+                .action(ArgAction::SetTrue)
+                .requires("dates"),
+        )
+        .arg(
+            Arg::new("allDays30")
+                .help("also test days 29 and 30 of every month with 'dates', e.g. February 30th")
+                .long("allDays30")
+                .action(ArgAction::SetTrue)
+                .requires("dates"),
+        )
+        .arg(
+            Arg::new("prefixes")
+                .help("put before the 'dates', can be repeated")
+                .long("prefixes")
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .requires("dates")
+                .required(false),
+        )
+        .arg(
+            Arg::new("suffixes")
+                .help("put after the 'dates', can be repeated")
+                .long("suffixes")
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .requires("dates")
                 .required(false),
         )
+        .args(CUSTOM_CHARSETS.iter().zip('1'..='4').map(|(name, i)| {
+            Arg::new(*name)
+                .help(format!("charset of the placeholder ?{} of the mask or template", i))
+                .long(*name)
+                .short(i)
+                .num_args(1)
+                .conflicts_with("restore")
+                .required(false)
+        }))
+        .arg(
+            Arg::new("increment")
+                .help("also test the passwords matching a prefix of the mask, shortest first")
+                .long("increment")
+                .action(ArgAction::SetTrue)
+                .requires("mask"),
+        )
+        .arg(
+            Arg::new("maskFirst")
+                .help("with a password dictionary, put the mask before the dictionary line instead of after it")
+                .long("maskFirst")
+                .action(ArgAction::SetTrue)
+                .requires_all(["mask", "passwordDictionary"]),
+        )
         .arg(
             Arg::new("minPasswordLen")
                 .value_parser(value_parser!(usize))
@@ -62,6 +290,7 @@ fn command() -> clap::Command {
                 .long("minPasswordLen")
                 .num_args(1)
                 .default_value("1")
+                .conflicts_with("restore")
                 .required(false),
         )
         .arg(
@@ -71,31 +300,114 @@ fn command() -> clap::Command {
                 .long("maxPasswordLen")
                 .num_args(1)
                 .default_value("8")
+                .conflicts_with("restore")
+                .required(false),
+        )
+        .arg(
+            Arg::new("skip")
+                .value_parser(value_parser!(usize))
+                .help("number of generated passwords to skip")
+                .long("skip")
+                .num_args(1)
+                .conflicts_with_all(["passwordDictionary", "stdin"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("startFrom")
+                .help("first generated password to test")
+                .long("startFrom")
+                .num_args(1)
+                .conflicts_with_all(["passwordDictionary", "stdin", "skip"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("limit")
+                .value_parser(value_parser!(usize))
+                .help("maximum number of generated passwords to test")
+                .long("limit")
+                .num_args(1)
+                .conflicts_with_all(["passwordDictionary", "stdin"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("session")
+                .help("path of the session file saved periodically and on Ctrl-C [default: <inputFile>.session, or the restored session file]")
+                .long("session")
+                .num_args(1)
+                .required(false),
+        )
+        .arg(
+            Arg::new("restore")
+                .help("continue the search saved in a session file")
+                .long("restore")
+                .num_args(1)
+                .conflicts_with_all(["inputFile", "passwordDictionary", "workers"])
                 .required(false),
         )
 }
 
 pub struct Arguments {
-    pub input_file: String,
+    pub input_file: Option<String>,
     pub workers: Option<usize>,
     pub charsets: Vec<char>,
     pub min_password_len: usize,
     pub max_password_len: usize,
-    pub password_dictionary: Option<String>,
+    pub password_dictionaries: Vec<String>,
+    pub dedup: bool,
+    pub stdin: bool,
+    pub rules: Vec<PathBuf>,
+    pub right_dictionary: Option<PathBuf>,
+    pub fragments: Vec<String>,
+    pub min_fragments: usize,
+    pub max_fragments: Option<usize>,
+    pub typos: Option<String>,
+    pub max_edits: usize,
+    pub separators: Vec<String>,
+    pub cases: Vec<WordCase>,
+    pub dict_encoding: DictEncoding,
+    pub encodings: Vec<PasswordEncoding>,
     pub custom_chars: Vec<char>,
+    pub mask: Option<String>,
+    pub template: Option<String>,
+    pub years: Option<(u32, u32)>,
+    pub date_layouts: Vec<DateLayout>,
+    pub unpadded: bool,
+    pub all_days_30: bool,
+    pub prefixes: Vec<String>,
+    pub suffixes: Vec<String>,
+    pub custom_charsets: Vec<String>,
+    pub increment: bool,
+    pub mask_first: bool,
+    pub skip: usize,
+    pub start_from: Option<String>,
+    pub limit: Option<usize>,
+    pub session: Option<String>,
+    pub restore: Option<String>,
 }
 
 pub fn get_args() -> Result<Arguments, CLIError> {
     let command = command();
     let matches = command.get_matches();
 
-    let input_file: &String = matches.get_one("inputFile").expect("impossible");
-    if !Path::new(input_file).is_file() {
-        return Err(CliArgumentError {
-            message: "'inputFile' does not exist".to_string(),
-        });
+    let input_file: Option<&String> = matches.try_get_one("inputFile")?;
+    if let Some(input_file) = input_file {
+        if !Path::new(input_file).is_file() {
+            return Err(CliArgumentError {
+                message: "'inputFile' does not exist".to_string(),
+            });
+        }
     }
 
+    let restore: Option<&String> = matches.try_get_one("restore")?;
+    if let Some(session_path) = restore {
+        if !Path::new(session_path).is_file() {
+            return Err(CliArgumentError {
+                message: "'restore' does not exist".to_string(),
+            });
+        }
+    }
+    let session = matches.try_get_one::<String>("session")?;
+
     let workers: Option<&usize> = matches.try_get_one("workers")?;
     if workers == Some(&0) {
         return Err(CliArgumentError {
@@ -103,15 +415,124 @@ pub fn get_args() -> Result<Arguments, CLIError> {
         });
     }
 
-    let password_dictionary = matches.try_get_one("passwordDictionary")?;
-    if let Some(dict_path) = password_dictionary {
-        if !Path::new(dict_path).is_file() {
+    let password_dictionaries: Vec<String> = match matches.try_get_many("passwordDictionary")? {
+        Some(paths) => paths.cloned().collect(),
+        None => vec![],
+    };
+    for dict_path in &password_dictionaries {
+        if !Path::new(dict_path).exists() {
+            return Err(CliArgumentError {
+                message: format!("'passwordDictionary' {} does not exist", dict_path),
+            });
+        }
+    }
+    let dedup = matches.get_flag("dedup");
+    let stdin = matches.get_flag("stdin");
+
+    let rules: Vec<PathBuf> = match matches.try_get_many::<String>("rules")? {
+        Some(paths) => paths.map(PathBuf::from).collect(),
+        None => vec![],
+    };
+    if !rules.is_empty() {
+        if password_dictionaries.is_empty() && !stdin {
             return Err(CliArgumentError {
-                message: "'passwordDictionary' does not exist".to_string(),
+                message: "'rules' requires 'passwordDictionary' or 'stdin'".to_string(),
             });
         }
+        // report syntax errors before the search starts
+        Rules::load(&rules).map_err(|e| CliArgumentError {
+            message: format!("'rules' {}", e),
+        })?;
+    }
+
+    let right_dictionary = matches
+        .try_get_one::<String>("rightDictionary")?
+        .map(PathBuf::from);
+    if let Some(right_dictionary) = &right_dictionary {
+        if !right_dictionary.is_file() {
+            return Err(CliArgumentError {
+                message: "'rightDictionary' does not exist".to_string(),
+            });
+        }
+        if password_dictionaries.len() != 1 || !Path::new(&password_dictionaries[0]).is_file() {
+            return Err(CliArgumentError {
+                message: "'rightDictionary' requires a single 'passwordDictionary' file"
+                    .to_string(),
+            });
+        }
+    }
+    let fragments: Vec<String> = match matches.try_get_many("fragments")? {
+        Some(tokens) => tokens.cloned().collect(),
+        None => vec![],
+    };
+    let min_fragments: &usize = matches.get_one("minFragments").expect("impossible");
+    let max_fragments: Option<&usize> = matches.try_get_one("maxFragments")?;
+    if !fragments.is_empty() {
+        // report a wrong number of pieces before the search starts
+        Fragments::new(&fragments, *min_fragments, max_fragments.copied(), &[], &[]).map_err(
+            |e| CliArgumentError {
+                message: format!("'fragments' {}", e),
+            },
+        )?;
+    }
+    let typos: Option<&String> = matches.try_get_one("typos")?;
+    let max_edits: &usize = matches.get_one("maxEdits").expect("impossible");
+    let separators: Vec<String> = match matches.try_get_many("separator")? {
+        Some(separators) => separators.cloned().collect(),
+        None => vec![],
+    };
+    let joined = right_dictionary.is_some() || !fragments.is_empty();
+    if !separators.is_empty() && !joined {
+        return Err(CliArgumentError {
+            message: "'separator' requires 'rightDictionary' or 'fragments'".to_string(),
+        });
+    }
+    if separators.len() > 1 && right_dictionary.is_some() {
+        return Err(CliArgumentError {
+            message: "'separator' can be repeated only with 'fragments'".to_string(),
+        });
+    }
+    let cases = match matches.try_get_many::<String>("cases")? {
+        Some(cases) => cases
+            .map(|c| c.parse())
+            .collect::<Result<Vec<WordCase>, _>>()
+            .map_err(|_| CliArgumentError {
+                message: format!("'cases' must be among {}", WordCase::to_string()),
+            })?
+            .into_iter()
+            .unique()
+            .collect(),
+        None => vec![],
+    };
+    if !cases.is_empty() && !joined {
+        return Err(CliArgumentError {
+            message: "'cases' requires 'rightDictionary' or 'fragments'".to_string(),
+        });
     }
 
+    let dict_encoding: &String = matches.get_one("dictEncoding").expect("impossible");
+    let dict_encoding = dict_encoding.parse().map_err(|_| CliArgumentError {
+        message: format!(
+            "'dictEncoding' must be one of {}",
+            DictEncoding::to_string()
+        ),
+    })?;
+
+    let encodings = matches
+        .get_many::<String>("encodings")
+        .unwrap()
+        .map(|e| e.parse())
+        .collect::<Result<Vec<PasswordEncoding>, _>>()
+        .map_err(|_| CliArgumentError {
+            message: format!(
+                "'encodings' must be among {}",
+                PasswordEncoding::to_string()
+            ),
+        })?
+        .into_iter()
+        .unique()
+        .collect();
+
     let charsets = matches
         .get_many::<String>("charsets")
         .unwrap()
@@ -135,6 +556,86 @@ pub fn get_args() -> Result<Arguments, CLIError> {
         _ => vec![],
     };
 
+    let custom_charsets: Vec<Option<String>> = (1..=4)
+        .map(|i| {
+            matches
+                .get_one::<String>(&format!("customCharset{}", i))
+                .cloned()
+        })
+        .collect();
+    let defined = custom_charsets
+        .iter()
+        .rposition(Option::is_some)
+        .map_or(0, |last| last + 1);
+    let custom_charsets = custom_charsets[..defined]
+        .iter()
+        .cloned()
+        .collect::<Option<Vec<String>>>()
+        .ok_or_else(|| CliArgumentError {
+            message: "custom charsets must be numbered from 1 without gaps".to_string(),
+        })?;
+    let mask: Option<&String> = matches.try_get_one("mask")?;
+    if let Some(mask) = mask {
+        Mask::parse(mask, &custom_charsets).map_err(|e| CliArgumentError {
+            message: format!("'mask' {}", e),
+        })?;
+    }
+    let template: Option<&String> = matches.try_get_one("template")?;
+    if let Some(template) = template {
+        Template::parse(template, &custom_charsets).map_err(|e| CliArgumentError {
+            message: format!("'template' {}", e),
+        })?;
+    }
+    if !custom_charsets.is_empty() && mask.is_none() && template.is_none() {
+        return Err(CliArgumentError {
+            message: "custom charsets require 'mask' or 'template'".to_string(),
+        });
+    }
+    let years = match matches.try_get_one::<String>("dates")? {
+        Some(years) => {
+            let (first, last) = years.split_once("..").unwrap_or((years, years));
+            match (first.parse(), last.parse()) {
+                (Ok(first), Ok(last)) => Some((first, last)),
+                _ => {
+                    return Err(CliArgumentError {
+                        message: "'dates' must be a year or a range of years like 1980..2000"
+                            .to_string(),
+                    })
+                }
+            }
+        }
+        None => None,
+    };
+    let date_layouts = match matches.try_get_many::<String>("dateLayouts")? {
+        Some(layouts) => layouts
+            .map(|l| l.parse())
+            .collect::<Result<Vec<DateLayout>, _>>()
+            .map_err(|_| CliArgumentError {
+                message: format!("'dateLayouts' must be among {}", DateLayout::to_string()),
+            })?
+            .into_iter()
+            .unique()
+            .collect(),
+        None => DateLayout::all(),
+    };
+    let unpadded = matches.get_flag("unpadded");
+    let all_days_30 = matches.get_flag("allDays30");
+    let affixes = |name: &str| -> Result<Vec<String>, CLIError> {
+        Ok(match matches.try_get_many::<String>(name)? {
+            Some(affixes) => affixes.cloned().collect(),
+            None => vec![],
+        })
+    };
+    let prefixes = affixes("prefixes")?;
+    let suffixes = affixes("suffixes")?;
+    if let Some((first_year, last_year)) = years {
+        Dates::check(first_year, last_year, &date_layouts).map_err(|e| CliArgumentError {
+            message: format!("'dates' {}", e),
+        })?;
+    }
+    let increment = matches.get_flag("increment");
+    let mask_first = matches.get_flag("maskFirst");
+
     let min_password_len = matches.get_one("minPasswordLen").expect("impossible");
     if *min_password_len == 0 {
         return Err(CliArgumentError {
@@ -155,14 +656,47 @@ pub fn get_args() -> Result<Arguments, CLIError> {
         });
     }
 
+    let skip: Option<&usize> = matches.try_get_one("skip")?;
+    let start_from: Option<&String> = matches.try_get_one("startFrom")?;
+    let limit: Option<&usize> = matches.try_get_one("limit")?;
+
     Ok(Arguments {
-        input_file: input_file.clone(),
+        input_file: input_file.cloned(),
         charsets,
         workers: workers.cloned(),
         min_password_len: *min_password_len,
         max_password_len: *max_password_len,
-        password_dictionary: password_dictionary.cloned(),
+        password_dictionaries,
+        dedup,
+        stdin,
+        rules,
+        right_dictionary,
+        fragments,
+        min_fragments: *min_fragments,
+        max_fragments: max_fragments.copied(),
+        typos: typos.cloned(),
+        max_edits: *max_edits,
+        separators,
+        cases,
+        dict_encoding,
+        encodings,
         custom_chars,
+        mask: mask.cloned(),
+        template: template.cloned(),
+        years,
+        date_layouts,
+        unpadded,
+        all_days_30,
+        prefixes,
+        suffixes,
+        custom_charsets,
+        increment,
+        mask_first,
+        skip: skip.cloned().unwrap_or(0),
+        start_from: start_from.cloned(),
+        limit: limit.cloned(),
+        session: session.cloned(),
+        restore: restore.cloned(),
     })
 }
 
@@ -8,8 +8,10 @@ pub enum CLIError {
     ClapError { e: clap::Error },
     #[error("CLI argument match error ({message})")]
     ClapMatchError { message: String },
-    #[error("CLI argument match error ({e})")]
+    #[error("{e}")]
     PasswordCrachError { e: Errors },
+    #[error("unable to handle Ctrl-C ({e})")]
+    CtrlCError { e: ctrlc::Error },
 }
 
 impl std::convert::From<clap::Error> for CLIError {
@@ -30,3 +32,8 @@ impl std::convert::From<Errors> for CLIError {
         CLIError::PasswordCrachError { e: e }
     }
 }
+impl std::convert::From<ctrlc::Error> for CLIError {
+    fn from(e: ctrlc::Error) -> Self {
+        CLIError::CtrlCError { e }
+    }
+}
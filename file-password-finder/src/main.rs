@@ -6,12 +6,20 @@ mod cli_error;
 use args::{get_args, Arguments};
 use cli_error::CLIError;
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
-use password_crack::{Cracker, Strategy};
+use password_crack::{
+    CandidateStream, Cracker, Event, Outcome, PasswordEncoding, PasswordGenerator, Session,
+    Strategy,
+};
 
-use std::sync::Arc;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-use std::{path::Path, process::exit};
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+};
 #[no_mangle]
 fn main() {
     let result = main_result();
@@ -30,57 +38,308 @@ fn main_result() -> Result<(), CLIError> {
         workers,
         min_password_len,
         max_password_len,
-        password_dictionary,
+        password_dictionaries,
+        dedup,
+        stdin,
+        rules,
+        right_dictionary,
+        fragments,
+        min_fragments,
+        max_fragments,
+        typos,
+        max_edits,
+        separators,
+        cases,
+        dict_encoding,
+        encodings,
         custom_chars,
+        mask,
+        template,
+        years,
+        date_layouts,
+        unpadded,
+        all_days_30,
+        prefixes,
+        suffixes,
+        custom_charsets,
+        increment,
+        mask_first,
+        skip,
+        start_from,
+        limit,
+        session,
+        restore,
     } = get_args()?;
-    let mut charsets = if custom_chars.len() > 0 {
-        custom_chars
-    } else {
-        charsets
-    };
-    charsets.sort();
-    charsets.dedup();
-    let strategy = match password_dictionary {
-        Some(dict_path) => {
-            let path = Path::new(&dict_path);
-            Strategy::PasswordFile(path.to_path_buf())
+    let (mut crack, session_path, streamed) = match restore {
+        Some(restore_path) => {
+            let saved = Session::load(Path::new(&restore_path))?;
+            let streamed = matches!(saved.strategy, Strategy::Stream { .. });
+            println!(
+                "Restoring {} - {} passwords already tested",
+                restore_path,
+                saved.tested_count()
+            );
+            println!("Starting {} workers to test passwords", saved.workers);
+            let session_path = session.unwrap_or(restore_path);
+            (Cracker::restore(saved)?, session_path, streamed)
         }
-        None => Strategy::GenPasswords {
-            charsets,
-            min_password_len,
-            max_password_len,
-        },
-    };
+        None => {
+            let input_file = input_file.expect("required without 'restore'");
+            let mut charsets = if custom_chars.len() > 0 {
+                custom_chars
+            } else {
+                charsets
+            };
+            charsets.sort();
+            charsets.dedup();
+            let strategy = match (password_dictionaries.as_slice(), mask) {
+                ([left], _) if right_dictionary.is_some() => Strategy::Combinator {
+                    left: PathBuf::from(left),
+                    right: right_dictionary.expect("checked by the guard"),
+                    encoding: dict_encoding,
+                    separator: separators.into_iter().next().unwrap_or_default(),
+                    cases,
+                },
+                ([], _) if years.is_some() => {
+                    let (first_year, last_year) = years.expect("checked by the guard");
+                    Strategy::Dates {
+                        first_year,
+                        last_year,
+                        layouts: date_layouts,
+                        unpadded,
+                        all_days_30,
+                        prefixes,
+                        suffixes,
+                    }
+                }
+                ([], _) if template.is_some() => Strategy::Template {
+                    template: template.expect("checked by the guard"),
+                    custom_charsets,
+                },
+                ([], _) if typos.is_some() => Strategy::Typos {
+                    password: typos.expect("checked by the guard"),
+                    max_edits,
+                    charset: charsets,
+                },
+                ([], _) if !fragments.is_empty() => Strategy::Fragments {
+                    tokens: fragments,
+                    max_tokens: max_fragments,
+                    min_tokens: min_fragments,
+                    separators,
+                    cases,
+                },
+                ([], _) if stdin => Strategy::Stream {
+                    source: CandidateStream::stdin(),
+                    encoding: dict_encoding,
+                    rules,
+                },
+                ([], Some(mask)) => Strategy::Mask {
+                    mask,
+                    custom_charsets,
+                    increment,
+                },
+                ([_, ..], Some(mask)) => Strategy::Hybrid {
+                    paths: password_dictionaries.iter().map(PathBuf::from).collect(),
+                    encoding: dict_encoding,
+                    mask,
+                    custom_charsets,
+                    increment,
+                    mask_first,
+                },
+                ([dict_path], _) if !dedup && Path::new(dict_path).is_file() => {
+                    let path = Path::new(dict_path);
+                    Strategy::PasswordFile {
+                        path: path.to_path_buf(),
+                        encoding: dict_encoding,
+                        rules,
+                    }
+                }
+                ([_, ..], _) => Strategy::Dictionaries {
+                    paths: password_dictionaries.iter().map(PathBuf::from).collect(),
+                    encoding: dict_encoding,
+                    dedup,
+                    rules,
+                },
+                ([], None) => {
+                    let skip = match start_from {
+                        Some(start_from) => PasswordGenerator::new(
+                            charsets.clone(),
+                            min_password_len,
+                            max_password_len,
+                        )
+                        .index_of(&start_from)
+                        .ok_or_else(|| CLIError::CliArgumentError {
+                            message: format!(
+                                "'startFrom' {} cannot be generated with the given charsets and lengths",
+                                start_from
+                            ),
+                        })?,
+                        None => skip,
+                    };
+                    Strategy::GenPasswords {
+                        charsets,
+                        min_password_len,
+                        max_password_len,
+                        skip,
+                        limit,
+                    }
+                }
+            };
 
-    let workers = workers.unwrap_or_else(num_cpus::get_physical);
-    println!("Starting {} workers to test passwords", workers);
+            let workers = workers.unwrap_or_else(num_cpus::get_physical);
+            println!("Starting {} workers to test passwords", workers);
+            let session_path = session.unwrap_or_else(|| format!("{}.session", input_file));
+            let mut crack = Cracker::new(input_file, workers, strategy);
+            crack.set_encodings(encodings);
+            (crack, session_path, stdin)
+        }
+    };
 
-    let crack = Arc::new(Cracker::new(input_file, workers, strategy));
     let count = crack.count()?;
-    let progress_bar = Arc::new(create_progress_bar(count as u64));
+    let progress_bar = Arc::new(create_progress_bar(count.map(|c| c as u64), streamed));
+    let progress_bar1 = Arc::clone(&progress_bar);
+    crack.subscribe(move |event| match event {
+        Event::TargetInfo {
+            description: Some(description),
+            ..
+        } => print_line(&progress_bar1, description),
+        Event::Progress { tested, .. } if count.is_some() => progress_bar1.set_position(*tested),
+        // unknown count, the bar follows the bytes read from the dictionary
+        Event::Progress { tested, per_second } => progress_bar1.set_message(format!(
+            "{} passwords throughput:{:.0}/s",
+            tested, per_second
+        )),
+        Event::DictionaryStarted {
+            path,
+            number,
+            total,
+        } if *total > 1 => print_line(
+            &progress_bar1,
+            format!("Reading dictionary {}/{}: {}", number, total, path.display()),
+        ),
+        Event::DictionaryProgress { read, size } => {
+            progress_bar1.set_length(*size);
+            progress_bar1.set_position(*read);
+        }
+        Event::DictionaryLines {
+            transcoded,
+            invalid,
+        } => {
+            if *transcoded > 0 {
+                print_line(
+                    &progress_bar1,
+                    format!("{} dictionary lines were transcoded to UTF-8", transcoded),
+                );
+            }
+            if *invalid > 0 {
+                print_line(
+                    &progress_bar1,
+                    format!(
+                        "{} dictionary lines were not valid in the dictionary encoding and were tested as raw bytes",
+                        invalid
+                    ),
+                );
+            }
+        }
+        _ => (),
+    });
+    let crack = Arc::new(crack);
+    let cancel_token = crack.cancel_token();
+    ctrlc::set_handler(move || cancel_token.cancel())?;
+    // guards the session file, true once the search is over
+    let session_finished = Arc::new(Mutex::new(false));
+    let session_finished1 = Arc::clone(&session_finished);
+    let session_path1 = session_path.clone();
     let progress_bar1 = Arc::clone(&progress_bar);
     let crack1 = Arc::clone(&crack);
     thread::spawn(move || loop {
-        thread::sleep(Duration::from_millis(500));
-        progress_bar1.set_position(crack1.tested_count());
+        thread::sleep(Duration::from_secs(30));
+        let session_finished = session_finished1.lock().unwrap();
+        if *session_finished {
+            break;
+        }
+        if let Err(e) = save_session(&crack1, &session_path1) {
+            print_line(&progress_bar1, format!("Unable to save the session: {}", e));
+        }
     });
-    match crack.start() {
-        Ok(Some(password)) => {
-            println!("Found password: {}", password);
+    // the standard input carries the passwords when they are streamed
+    if !streamed {
+        listen_pause_keys(&crack, &progress_bar);
+    }
+    let outcome = crack.start();
+    let mut session_finished = session_finished.lock().unwrap();
+    *session_finished = true;
+    match outcome {
+        Ok(Outcome::Found { password, encoding }) => {
+            if encoding == PasswordEncoding::Utf8 {
+                println!("Found password: {}", password);
+            } else {
+                println!("Found password: {} (encoded as {})", password, encoding);
+            }
+            let _ = fs::remove_file(&session_path);
         }
-        Ok(None) => {
+        Ok(Outcome::NotFound) => {
             println!("Password not found");
+            let _ = fs::remove_file(&session_path);
+        }
+        Ok(Outcome::Cancelled { tested }) => {
+            println!("Search cancelled after testing {} passwords", tested);
+            save_session(&crack, &session_path)?;
+            println!("Continue with --restore {}", session_path);
         }
-        Err(_) => {}
+        Err(e) => return Err(e.into()),
     };
     Ok(())
 }
 
-pub fn create_progress_bar(len: u64) -> ProgressBar {
+fn listen_pause_keys(crack: &Arc<Cracker>, progress_bar: &Arc<ProgressBar>) {
+    println!("Enter 'p' to pause and 'r' to resume");
+    let progress_bar = Arc::clone(progress_bar);
+    let crack = Arc::clone(crack);
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            match line.as_deref().map(str::trim) {
+                Ok("p") => {
+                    crack.pause();
+                    print_line(&progress_bar, "Paused");
+                }
+                Ok("r") => {
+                    crack.resume();
+                    print_line(&progress_bar, "Resumed");
+                }
+                Ok(_) => (),
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+fn save_session(crack: &Cracker, session_path: &str) -> Result<(), CLIError> {
+    crack.checkpoint()?.save(Path::new(session_path))?;
+    Ok(())
+}
+
+/// Prints above the progress bar, or directly when the bar is hidden (e.g. stdout is not a terminal).
+fn print_line(progress_bar: &ProgressBar, message: impl AsRef<str>) {
+    if progress_bar.is_hidden() {
+        println!("{}", message.as_ref());
+    } else {
+        progress_bar.println(message);
+    }
+}
+
+/// Progress in passwords, or in dictionary bytes when the number of passwords is unknown.
+/// Streamed passwords have neither and only show the throughput.
+pub fn create_progress_bar(len: Option<u64>, streamed: bool) -> ProgressBar {
     //设置进度条 进度条的样式也会影响性能，进度条越简单性能也好，影响比较小
-    let progress_bar = ProgressBar::new(len);
+    let progress_bar = ProgressBar::new(len.unwrap_or(0));
+    let template = match len {
+        None if streamed => "[{elapsed_precise}] {spinner} {msg}",
+        Some(_) => "[{elapsed_precise}] {bar} {pos}/{len} throughput:{per_sec} (eta:{eta})",
+        None => "[{elapsed_precise}] {bar} {bytes}/{total_bytes} {msg} (eta:{eta})",
+    };
     let progress_style = ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] {bar} {pos}/{len} throughput:{per_sec} (eta:{eta})")
+        .template(template)
         .expect("Failed to create progress style");
     progress_bar.set_style(progress_style);
     //每两秒刷新终端，避免闪烁
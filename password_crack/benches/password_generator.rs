@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use password_crack::{charset_lowercase_letters, PasswordGenerator};
+use password_crack::{charset_lowercase_letters, Candidates, PasswordGenerator};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("generate_password", |b| {
@@ -12,6 +12,18 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             let _last = black_box(iterator.last());
         })
     });
+    c.bench_function("fill_password_buffer", |b| {
+        let charset = charset_lowercase_letters();
+        let min_password_len = 3;
+        let max_password_len = 5;
+        b.iter(|| {
+            let mut generator =
+                PasswordGenerator::new(charset.clone(), min_password_len, max_password_len);
+            let mut buffer = Vec::new();
+            while generator.next_into(&mut buffer).unwrap() {}
+            black_box(buffer)
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,147 @@
+use crate::errors::Errors;
+
+/// Source of password candidates filling a buffer owned by the worker.
+///
+/// Unlike an [`Iterator`] of `String`, the same buffer is reused for every candidate
+/// so that no allocation happens on the hot path.
+pub trait Candidates: Send {
+    /// Replaces the content of `buffer` with the next candidate, returns `false` once exhausted.
+    /// An error stops the whole search and is returned by [`crate::Cracker::start`].
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors>;
+
+    /// Skips the next `n` candidates, returns `false` if fewer were left.
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        let mut buffer = Vec::new();
+        for _ in 0..n {
+            if !self.next_into(&mut buffer)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether the candidate just filled was discarded, e.g. by a rejection rule, and must not
+    /// be tested. It still counts as one candidate so that the positions of the workers hold.
+    fn is_rejected(&self) -> bool {
+        false
+    }
+}
+
+pub type Passwords = Box<dyn Candidates>;
+
+/// Candidates of one worker: every `worker_count`-th candidate, starting from index `start`.
+pub fn shard(passwords: Passwords, worker_count: usize, start: usize) -> Passwords {
+    if worker_count > 1 || start > 0 {
+        Box::new(Shard {
+            passwords,
+            skip: start,
+            step: worker_count - 1,
+        })
+    } else {
+        passwords
+    }
+}
+
+struct Shard {
+    passwords: Passwords,
+    /// candidates to skip before the next one
+    skip: usize,
+    step: usize,
+}
+impl Candidates for Shard {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.skip > 0 && !self.passwords.advance_by(self.skip)? {
+            self.skip = 0;
+            return Ok(false);
+        }
+        self.skip = self.step;
+        self.passwords.next_into(buffer)
+    }
+
+    fn is_rejected(&self) -> bool {
+        self.passwords.is_rejected()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{shard, Candidates, Passwords};
+    use crate::errors::Errors;
+
+    /// Every candidate left in `candidates`, the rejected ones as empty strings.
+    pub(crate) fn collect(mut candidates: Passwords) -> Vec<String> {
+        let mut buffer = Vec::new();
+        let mut collected = vec![];
+        while candidates.next_into(&mut buffer).unwrap() {
+            if candidates.is_rejected() {
+                collected.push(String::new());
+            } else {
+                collected.push(String::from_utf8(buffer.clone()).unwrap());
+            }
+        }
+        collected
+    }
+
+    /// Checks that each of `workers` workers gets its share of the candidates built by `make`,
+    /// in order, whatever its shard start.
+    pub(crate) fn assert_shards_match(make: impl Fn() -> Passwords, workers: usize) {
+        let all = collect(make());
+        for start in 0..workers {
+            let expected: Vec<String> = all.iter().skip(start).step_by(workers).cloned().collect();
+            assert_eq!(
+                collect(shard(make(), workers, start)),
+                expected,
+                "worker {} of {}",
+                start,
+                workers
+            );
+        }
+    }
+
+    /// `words` in order, like the lines of a dictionary.
+    pub(crate) fn words(words: &'static [&'static str]) -> Passwords {
+        Box::new(Words(words.iter()))
+    }
+
+    struct Words(std::slice::Iter<'static, &'static str>);
+    impl Candidates for Words {
+        fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+            match self.0.next() {
+                Some(word) => {
+                    buffer.clear();
+                    buffer.extend_from_slice(word.as_bytes());
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+
+    struct Numbers(usize, usize);
+    impl Candidates for Numbers {
+        fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+            if self.0 == self.1 {
+                return Ok(false);
+            }
+            buffer.clear();
+            buffer.extend_from_slice(self.0.to_string().as_bytes());
+            self.0 += 1;
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn shard_by_index() {
+        let mut passwords = shard(Box::new(Numbers(0, 10)), 3, 4);
+        let mut buffer = Vec::new();
+        let mut sharded = vec![];
+        while passwords.next_into(&mut buffer).unwrap() {
+            sharded.push(String::from_utf8(buffer.clone()).unwrap());
+        }
+        assert_eq!(sharded, vec!["4", "7"]);
+        assert!(!passwords.next_into(&mut buffer).unwrap());
+        for workers in 1..=4 {
+            assert_shards_match(|| Box::new(Numbers(0, 10)), workers);
+        }
+    }
+}
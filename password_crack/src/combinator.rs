@@ -0,0 +1,263 @@
+use std::{path::Path, sync::Arc};
+
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    candidates::{Candidates, Passwords},
+    errors::Errors,
+    password_reader::{load_lines, DictEncoding},
+};
+
+/// Case applied to both words of a combinator candidate.
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Display, FromStr, Serialize, Deserialize,
+)]
+#[display(style = "kebab-case")]
+pub enum WordCase {
+    /// the words as they are in the dictionaries
+    #[default]
+    AsIs,
+    Lower,
+    Upper,
+    /// first letter upper case, the others lower case, e.g. `ZhangSan`
+    Capitalize,
+}
+impl WordCase {
+    pub fn to_string() -> String {
+        format!(
+            "{},{},{},{}",
+            WordCase::AsIs,
+            WordCase::Lower,
+            WordCase::Upper,
+            WordCase::Capitalize
+        )
+    }
+    /// Appends `word` in this case to `buffer`. Words which are not UTF-8 only have
+    /// their ASCII letters changed.
+    pub fn push(self, word: &[u8], buffer: &mut Vec<u8>) {
+        let word = match std::str::from_utf8(word) {
+            Ok(word) => word,
+            Err(_) => {
+                let start = buffer.len();
+                buffer.extend_from_slice(word);
+                let cased = &mut buffer[start..];
+                match self {
+                    WordCase::AsIs => {}
+                    WordCase::Lower => cased.make_ascii_lowercase(),
+                    WordCase::Upper => cased.make_ascii_uppercase(),
+                    WordCase::Capitalize => {
+                        cased.make_ascii_lowercase();
+                        if let Some(first) = cased.first_mut() {
+                            first.make_ascii_uppercase();
+                        }
+                    }
+                }
+                return;
+            }
+        };
+        let cased = match self {
+            WordCase::AsIs => word.to_string(),
+            WordCase::Lower => word.to_lowercase(),
+            WordCase::Upper => word.to_uppercase(),
+            WordCase::Capitalize => {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.as_str().to_lowercase().chars())
+                        .collect(),
+                    None => String::new(),
+                }
+            }
+        };
+        buffer.extend_from_slice(cased.as_bytes());
+    }
+}
+
+/// Words of the right dictionary of a combinator search, joined to every line of the left one.
+///
+/// The right dictionary is kept in memory so that every worker can jump to any pair by index.
+pub struct Combinator {
+    separator: Vec<u8>,
+    cases: Vec<WordCase>,
+    /// per case, every right word in that case
+    right: Vec<Vec<Vec<u8>>>,
+}
+impl Combinator {
+    pub fn load(
+        right: &Path,
+        encoding: DictEncoding,
+        separator: &str,
+        cases: &[WordCase],
+    ) -> Result<Self, Errors> {
+        let words = load_lines(right, encoding)?;
+        let cases = if cases.is_empty() {
+            vec![WordCase::AsIs]
+        } else {
+            cases.to_vec()
+        };
+        let right = cases
+            .iter()
+            .map(|case| {
+                words
+                    .iter()
+                    .map(|word| {
+                        let mut cased = Vec::with_capacity(word.len());
+                        case.push(word, &mut cased);
+                        cased
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Combinator {
+            separator: separator.as_bytes().to_vec(),
+            cases,
+            right,
+        })
+    }
+    /// Number of candidates derived from one left word.
+    pub fn count(&self) -> usize {
+        self.cases.len() * self.right[0].len()
+    }
+}
+
+/// Every left word joined to every right word in every case: the candidate `k` of a left
+/// word is the right word `k / cases` in the case `k % cases`.
+pub struct CombinatorCandidates {
+    words: Passwords,
+    combinator: Arc<Combinator>,
+    count: usize,
+    word: Vec<u8>,
+    /// per case, the current left word in that case
+    left: Vec<Vec<u8>>,
+    /// index of the next candidate, `count` to read the next word first
+    next: usize,
+}
+impl CombinatorCandidates {
+    pub fn new(words: Passwords, combinator: Arc<Combinator>) -> Self {
+        let count = combinator.count();
+        let left = vec![Vec::new(); combinator.cases.len()];
+        CombinatorCandidates {
+            words,
+            combinator,
+            count,
+            word: Vec::new(),
+            left,
+            next: count,
+        }
+    }
+    fn read_word(&mut self) -> Result<bool, Errors> {
+        if !self.words.next_into(&mut self.word)? {
+            return Ok(false);
+        }
+        for (case, left) in self.combinator.cases.iter().zip(&mut self.left) {
+            left.clear();
+            case.push(&self.word, left);
+        }
+        Ok(true)
+    }
+}
+impl Candidates for CombinatorCandidates {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.count == 0 {
+            return Ok(false);
+        }
+        if self.next == self.count {
+            if !self.read_word()? {
+                return Ok(false);
+            }
+            self.next = 0;
+        }
+        let cases = self.combinator.cases.len();
+        let case = self.next % cases;
+        buffer.clear();
+        buffer.extend_from_slice(&self.left[case]);
+        buffer.extend_from_slice(&self.combinator.separator);
+        buffer.extend_from_slice(&self.combinator.right[case][self.next / cases]);
+        self.next += 1;
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        if self.count == 0 {
+            return Ok(n == 0);
+        }
+        let left = self.count - self.next;
+        if n <= left {
+            self.next += n;
+            return Ok(true);
+        }
+        // skip whole left words without combining them
+        let n = n - left;
+        self.next = self.count;
+        if !self.words.advance_by(n / self.count)? {
+            return Ok(false);
+        }
+        if n % self.count > 0 {
+            if !self.read_word()? {
+                return Ok(false);
+            }
+            self.next = n % self.count;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{Combinator, CombinatorCandidates, WordCase};
+    use crate::{
+        candidates::test::{assert_shards_match, collect, words},
+        DictEncoding,
+    };
+
+    #[test]
+    fn cases() {
+        let mut buffer = Vec::new();
+        WordCase::Capitalize.push("zHANG".as_bytes(), &mut buffer);
+        WordCase::Upper.push("straße".as_bytes(), &mut buffer);
+        WordCase::Lower.push(b"\xffAB", &mut buffer);
+        assert_eq!(buffer, b"ZhangSTRASSE\xffab");
+        assert_eq!("as-is".parse(), Ok(WordCase::AsIs));
+    }
+
+    #[test]
+    fn every_pair_by_index() {
+        let path = std::env::temp_dir().join("password_crack_combinator_right.txt");
+        std::fs::write(&path, "san\r\nSi\n").unwrap();
+        let combinator = Arc::new(
+            Combinator::load(
+                &path,
+                DictEncoding::Utf8,
+                "_",
+                &[WordCase::AsIs, WordCase::Capitalize],
+            )
+            .unwrap(),
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(combinator.count(), 4);
+        let candidates = || {
+            Box::new(CombinatorCandidates::new(
+                words(&["zhang", "li"]),
+                combinator.clone(),
+            )) as _
+        };
+        assert_eq!(
+            collect(candidates()),
+            vec![
+                "zhang_san",
+                "Zhang_San",
+                "zhang_Si",
+                "Zhang_Si",
+                "li_san",
+                "Li_San",
+                "li_Si",
+                "Li_Si"
+            ]
+        );
+        assert_shards_match(candidates, 3);
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+/// Cloneable handle stopping a running search from another thread.
+///
+/// Workers notice the cancellation at their next batch boundary, see [`crate::Verifier::batch_size`].
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Cloneable handle parking the workers of a running search until resumed.
+///
+/// Workers park at their next batch boundary and keep their position in the candidate stream.
+#[derive(Clone, Debug, Default)]
+pub struct PauseToken {
+    paused: Arc<(Mutex<bool>, Condvar)>,
+}
+impl PauseToken {
+    pub fn new() -> Self {
+        PauseToken::default()
+    }
+    pub fn pause(&self) {
+        *self.paused.0.lock().unwrap() = true;
+    }
+    pub fn resume(&self) {
+        let (lock, condvar) = &*self.paused;
+        *lock.lock().unwrap() = false;
+        condvar.notify_all();
+    }
+    pub fn is_paused(&self) -> bool {
+        *self.paused.0.lock().unwrap()
+    }
+    /// Blocks the calling worker while paused, returns early once `stopped` is true.
+    pub(crate) fn wait_while_paused(&self, stopped: impl Fn() -> bool) {
+        let (lock, condvar) = &*self.paused;
+        let mut paused = lock.lock().unwrap();
+        while *paused && !stopped() {
+            // wake up regularly as stopping does not notify the condition variable
+            paused = condvar
+                .wait_timeout(paused, Duration::from_millis(100))
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use super::{CancelToken, PauseToken};
+
+    #[test]
+    fn pause_blocks_until_resumed() {
+        let pause_token = PauseToken::new();
+        pause_token.pause();
+        let worker_token = pause_token.clone();
+        let worker = thread::spawn(move || worker_token.wait_while_paused(|| false));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!worker.is_finished());
+        pause_token.resume();
+        worker.join().unwrap();
+        assert!(!pause_token.is_paused());
+    }
+
+    #[test]
+    fn cancel_releases_paused_worker() {
+        let pause_token = PauseToken::new();
+        pause_token.pause();
+        let cancel_token = CancelToken::new();
+        cancel_token.cancel();
+        pause_token.wait_while_paused(|| cancel_token.is_cancelled());
+        assert!(pause_token.is_paused());
+    }
+}
@@ -0,0 +1,511 @@
+use std::sync::Arc;
+
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+
+use crate::{candidates::Candidates, errors::Errors};
+
+/// How a date is written, e.g. `19900507` for May 7th 1990 in `yyyymmdd`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Display, FromStr, Serialize, Deserialize)]
+pub enum DateLayout {
+    #[display("yyyymmdd")]
+    Yyyymmdd,
+    #[display("ddmmyyyy")]
+    Ddmmyyyy,
+    #[display("mmddyyyy")]
+    Mmddyyyy,
+    #[display("yymmdd")]
+    Yymmdd,
+    #[display("mmddyy")]
+    Mmddyy,
+    #[display("yyyy-mm-dd")]
+    YyyyMmDd,
+}
+impl DateLayout {
+    pub fn to_string() -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            DateLayout::Yyyymmdd,
+            DateLayout::Ddmmyyyy,
+            DateLayout::Mmddyyyy,
+            DateLayout::Yymmdd,
+            DateLayout::Mmddyy,
+            DateLayout::YyyyMmDd
+        )
+    }
+    pub fn all() -> Vec<DateLayout> {
+        vec![
+            DateLayout::Yyyymmdd,
+            DateLayout::Ddmmyyyy,
+            DateLayout::Mmddyyyy,
+            DateLayout::Yymmdd,
+            DateLayout::Mmddyy,
+            DateLayout::YyyyMmDd,
+        ]
+    }
+    /// Fields in the order they are written, and what is written between them.
+    fn fields(self) -> ([Field; 3], &'static [u8]) {
+        match self {
+            DateLayout::Yyyymmdd => ([Field::Year, Field::Month, Field::Day], b""),
+            DateLayout::Ddmmyyyy => ([Field::Day, Field::Month, Field::Year], b""),
+            DateLayout::Mmddyyyy => ([Field::Month, Field::Day, Field::Year], b""),
+            DateLayout::Yymmdd => ([Field::ShortYear, Field::Month, Field::Day], b""),
+            DateLayout::Mmddyy => ([Field::Month, Field::Day, Field::ShortYear], b""),
+            DateLayout::YyyyMmDd => ([Field::Year, Field::Month, Field::Day], b"-"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Field {
+    Year,
+    ShortYear,
+    Month,
+    Day,
+}
+
+/// A date written in one of the layouts of a search, ordered like the search tests them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Written {
+    year: u16,
+    month: u8,
+    day: u8,
+    /// without the leading zeros of the month and day
+    unpadded: bool,
+    /// index in the layouts of the search
+    layout: u8,
+}
+impl Written {
+    /// Appends the date to `buffer`.
+    fn push(self, layouts: &[DateLayout], buffer: &mut Vec<u8>) {
+        let width = if self.unpadded { 1 } else { 2 };
+        let (fields, separator) = layouts[self.layout as usize].fields();
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                buffer.extend_from_slice(separator);
+            }
+            match field {
+                Field::Year => push_digits(u32::from(self.year), 4, buffer),
+                Field::ShortYear => push_digits(u32::from(self.year) % 100, 2, buffer),
+                Field::Month => push_digits(u32::from(self.month), width, buffer),
+                Field::Day => push_digits(u32::from(self.day), width, buffer),
+            }
+        }
+    }
+}
+
+/// Appends `number` with at least `width` digits.
+fn push_digits(number: u32, width: u32, buffer: &mut Vec<u8>) {
+    let digits = number.checked_ilog10().unwrap_or(0) + 1;
+    for i in (0..digits.max(width)).rev() {
+        buffer.push(b'0' + (number / 10u32.pow(i) % 10) as u8);
+    }
+}
+
+fn parse_digits(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |number, &digit| {
+        digit
+            .is_ascii_digit()
+            .then(|| number * 10 + u32::from(digit - b'0'))
+    })
+}
+
+/// The dates of a search and the ways to write them.
+struct Calendar {
+    first_year: u32,
+    last_year: u32,
+    layouts: Vec<DateLayout>,
+    unpadded: bool,
+    all_days_30: bool,
+    /// every way to read a date back from its text
+    readings: Vec<Reading>,
+}
+
+/// A layout with the number of digits of the month and the day.
+struct Reading {
+    layout: u8,
+    unpadded: bool,
+    month_width: usize,
+    day_width: usize,
+    /// length of the text of the date
+    len: usize,
+}
+impl Reading {
+    fn width(&self, field: Field) -> usize {
+        match field {
+            Field::Year => 4,
+            Field::ShortYear => 2,
+            Field::Month => self.month_width,
+            Field::Day => self.day_width,
+        }
+    }
+}
+
+impl Calendar {
+    fn new(
+        first_year: u32,
+        last_year: u32,
+        layouts: &[DateLayout],
+        unpadded: bool,
+        all_days_30: bool,
+    ) -> Self {
+        let mut distinct_layouts = Vec::with_capacity(layouts.len());
+        for layout in layouts {
+            if !distinct_layouts.contains(layout) {
+                distinct_layouts.push(*layout);
+            }
+        }
+        let mut readings = vec![];
+        for (layout, date_layout) in distinct_layouts.iter().enumerate() {
+            let (fields, separator) = date_layout.fields();
+            // two digits months and days are never unpadded
+            let widths: &[(bool, usize, usize)] = if unpadded {
+                &[(false, 2, 2), (true, 1, 1), (true, 1, 2), (true, 2, 1)]
+            } else {
+                &[(false, 2, 2)]
+            };
+            for &(unpadded, month_width, day_width) in widths {
+                let mut reading = Reading {
+                    layout: layout as u8,
+                    unpadded,
+                    month_width,
+                    day_width,
+                    len: 2 * separator.len(),
+                };
+                reading.len += fields.iter().map(|&f| reading.width(f)).sum::<usize>();
+                readings.push(reading);
+            }
+        }
+        Calendar {
+            first_year,
+            last_year,
+            layouts: distinct_layouts,
+            unpadded,
+            all_days_30,
+            readings,
+        }
+    }
+
+    fn days(&self, year: u32, month: u32) -> u32 {
+        if self.all_days_30 {
+            days_in_month(year, month).max(30)
+        } else {
+            days_in_month(year, month)
+        }
+    }
+
+    /// Every way to write every date, in the order of the search.
+    fn writings(&self) -> impl Iterator<Item = Written> + '_ {
+        (self.first_year..=self.last_year).flat_map(move |year| {
+            (1..=12).flat_map(move |month| {
+                (1..=self.days(year, month)).flat_map(move |day| {
+                    // the same as padded for two digits months and days
+                    let unpadded = self.unpadded && (month < 10 || day < 10);
+                    [false, true]
+                        .into_iter()
+                        .filter(move |u| !u || unpadded)
+                        .flat_map(move |unpadded| {
+                            (0..self.layouts.len()).map(move |layout| Written {
+                                year: year as u16,
+                                month: month as u8,
+                                day: day as u8,
+                                unpadded,
+                                layout: layout as u8,
+                            })
+                        })
+                })
+            })
+        })
+    }
+
+    /// Whether no date comes before `written` in the search with the same `text`,
+    /// e.g. `01022001` is January 2nd in `mmddyyyy` after February 1st in `ddmmyyyy`.
+    fn is_first(&self, written: Written, text: &[u8]) -> bool {
+        self.readings
+            .iter()
+            .filter(|reading| reading.len == text.len())
+            .filter_map(|reading| self.read(text, reading))
+            .all(|other| other >= written)
+    }
+
+    /// The first date of the search written as `text` the way of `reading`, if any.
+    fn read(&self, text: &[u8], reading: &Reading) -> Option<Written> {
+        let (fields, separator) = self.layouts[reading.layout as usize].fields();
+        let (mut year, mut short_year, mut month, mut day) = (None, None, 0, 0);
+        let mut rest = text;
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                rest = rest.strip_prefix(separator)?;
+            }
+            let (digits, after) = rest.split_at(reading.width(field));
+            rest = after;
+            // an unpadded month or day has no leading zero
+            if reading.unpadded && matches!(field, Field::Month | Field::Day) && digits[0] == b'0' {
+                return None;
+            }
+            let value = parse_digits(digits)?;
+            match field {
+                Field::Year => year = Some(value),
+                Field::ShortYear => short_year = Some(value),
+                Field::Month => month = value,
+                Field::Day => day = value,
+            }
+        }
+        if !(1..=12).contains(&month) || day == 0 {
+            return None;
+        }
+        let year = match (year, short_year) {
+            (Some(year), _) => Some(year).filter(|year| {
+                (self.first_year..=self.last_year).contains(year) && day <= self.days(*year, month)
+            }),
+            // the first century with this date
+            (None, Some(short_year)) => {
+                let first = self.first_year + (short_year + 100 - self.first_year % 100) % 100;
+                (first..=self.last_year)
+                    .step_by(100)
+                    .find(|&year| day <= self.days(year, month))
+            }
+            (None, None) => None,
+        }?;
+        Some(Written {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            unpadded: reading.unpadded,
+            layout: reading.layout,
+        })
+    }
+}
+
+/// Calendar dates of a range of years, such as birthdays, written in several layouts.
+///
+/// The dates are tested year by year, each in every layout, then without the leading zeros
+/// of its month and day with `unpadded`. With `all_days_30`, days 29 and 30 of every month
+/// are tested too, e.g. February 30th.
+/// Dates written the same way in several layouts, e.g. `01022001` for January 2nd and
+/// February 1st, are only tested once. Every date is tested after each prefix and before
+/// each suffix.
+#[derive(Clone)]
+pub struct Dates {
+    dates: Arc<Vec<Written>>,
+    layouts: Vec<DateLayout>,
+    prefixes: Vec<Vec<u8>>,
+    suffixes: Vec<Vec<u8>>,
+}
+impl Dates {
+    pub fn new(
+        first_year: u32,
+        last_year: u32,
+        layouts: &[DateLayout],
+        unpadded: bool,
+        all_days_30: bool,
+        prefixes: &[String],
+        suffixes: &[String],
+    ) -> Result<Self, Errors> {
+        Dates::check(first_year, last_year, layouts)?;
+        let calendar = Calendar::new(first_year, last_year, layouts, unpadded, all_days_30);
+        let mut text = Vec::new();
+        let dates = calendar
+            .writings()
+            .filter(|written| {
+                text.clear();
+                written.push(&calendar.layouts, &mut text);
+                calendar.is_first(*written, &text)
+            })
+            .collect();
+        let affixes = |affixes: &[String]| {
+            if affixes.is_empty() {
+                vec![Vec::new()]
+            } else {
+                affixes.iter().map(|a| a.as_bytes().to_vec()).collect()
+            }
+        };
+        Ok(Dates {
+            dates: Arc::new(dates),
+            layouts: calendar.layouts,
+            prefixes: affixes(prefixes),
+            suffixes: affixes(suffixes),
+        })
+    }
+
+    /// Fails like [`Dates::new`] on invalid years or layouts, without generating the dates.
+    pub fn check(first_year: u32, last_year: u32, layouts: &[DateLayout]) -> Result<(), Errors> {
+        if first_year > last_year || last_year > 9999 {
+            return Err(Errors::invalid_dates_error(format!(
+                "years {}..{} must be increasing and of at most 4 digits",
+                first_year, last_year
+            )));
+        }
+        if layouts.is_empty() {
+            return Err(Errors::invalid_dates_error("no layout".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn count(&self) -> Result<usize, Errors> {
+        self.dates
+            .len()
+            .checked_mul(self.prefixes.len())
+            .and_then(|c| c.checked_mul(self.suffixes.len()))
+            .ok_or(Errors::MathError {
+                message: String::from("算术溢出"),
+            })
+    }
+
+    pub fn generator(&self) -> Result<DateGenerator, Errors> {
+        Ok(DateGenerator {
+            count: self.count()?,
+            dates: self.clone(),
+            next: 0,
+        })
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Candidates of [`Dates`], the suffix changing first, then the prefix, then the date.
+pub struct DateGenerator {
+    dates: Dates,
+    count: usize,
+    next: usize,
+}
+impl Candidates for DateGenerator {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next >= self.count {
+            return Ok(false);
+        }
+        let suffixes = self.dates.suffixes.len();
+        let affixes = self.dates.prefixes.len() * suffixes;
+        buffer.clear();
+        buffer.extend_from_slice(&self.dates.prefixes[self.next % affixes / suffixes]);
+        self.dates.dates[self.next / affixes].push(&self.dates.layouts, buffer);
+        buffer.extend_from_slice(&self.dates.suffixes[self.next % suffixes]);
+        self.next += 1;
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        self.next = self.next.saturating_add(n);
+        Ok(self.next <= self.count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{days_in_month, DateLayout, Dates};
+    use crate::candidates::test::{assert_shards_match, collect};
+
+    #[test]
+    fn valid_dates() {
+        let dates = Dates::new(1900, 2000, &[DateLayout::Yyyymmdd], false, false, &[], &[]);
+        // 1900 is not a leap year, 2000 is
+        assert_eq!(dates.unwrap().count().unwrap(), 101 * 365 + 25);
+        let dates = Dates::new(2001, 2001, &DateLayout::all(), false, false, &[], &[]).unwrap();
+        let all = collect(Box::new(dates.generator().unwrap()));
+        // ddmmyyyy and mmddyyyy share the 12 * 12 dates whose day could be a month,
+        // yymmdd and mmddyy the first day of every month with January 1st to 12th
+        assert_eq!(all.len(), 365 * 6 - 144 - 12);
+        assert_eq!(dates.count().unwrap(), all.len());
+        assert_eq!(all[..4], ["20010101", "01012001", "010101", "2001-01-01"]);
+        assert_eq!(all[all.len() - 1], "2001-12-31");
+        assert!(!all.contains(&"20010229".to_string()));
+        assert!(Dates::new(2001, 2000, &DateLayout::all(), false, false, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn variants() {
+        let dates = Dates::new(
+            2001,
+            2001,
+            &[DateLayout::Mmddyy],
+            true,
+            true,
+            &["a".to_string()],
+            &["".to_string(), "!".to_string()],
+        )
+        .unwrap();
+        // February gets days 29 and 30 with `all_days_30`; without leading zeros, every day of
+        // the months 1 to 9 and the days 1 to 9 of the months 10 to 12, but January 11th to 19th
+        // and 21st to 29th are written like some days of November and December, e.g. `11501`
+        let dates_count = 367 + 275 + 3 * 9 - 2 * 9;
+        assert_eq!(dates.count().unwrap(), dates_count * 2);
+        let all = collect(Box::new(dates.generator().unwrap()));
+        assert_eq!(all[..4], ["a010101", "a010101!", "a1101", "a1101!"]);
+        assert!(all.contains(&"a023001".to_string()));
+        assert!(all.contains(&"a123101!".to_string()));
+        assert_shards_match(|| Box::new(dates.generator().unwrap()), 3);
+    }
+
+    #[test]
+    fn same_as_skipping_seen_dates() {
+        // the centuries share the dates written with two digits years, 1900 is not a leap year
+        let searches = [
+            (0, 101, DateLayout::all(), true, false),
+            (1899, 2001, DateLayout::all(), true, true),
+            (
+                1899,
+                2001,
+                vec![DateLayout::Mmddyy, DateLayout::Yymmdd, DateLayout::Mmddyy],
+                false,
+                false,
+            ),
+        ];
+        for (first_year, last_year, layouts, unpadded, all_days_30) in searches {
+            let mut seen = HashSet::new();
+            let mut expected = vec![];
+            for year in first_year..=last_year {
+                for month in 1..=12 {
+                    let days = days_in_month(year, month).max(if all_days_30 { 30 } else { 0 });
+                    for day in 1..=days {
+                        for padded in [true, false] {
+                            if !padded && (!unpadded || (month >= 10 && day >= 10)) {
+                                continue;
+                            }
+                            let (m, d) = if padded {
+                                (format!("{:02}", month), format!("{:02}", day))
+                            } else {
+                                (month.to_string(), day.to_string())
+                            };
+                            let yy = format!("{:02}", year % 100);
+                            for layout in &layouts {
+                                let date = match layout {
+                                    DateLayout::Yyyymmdd => format!("{:04}{}{}", year, m, d),
+                                    DateLayout::Ddmmyyyy => format!("{}{}{:04}", d, m, year),
+                                    DateLayout::Mmddyyyy => format!("{}{}{:04}", m, d, year),
+                                    DateLayout::Yymmdd => format!("{}{}{}", yy, m, d),
+                                    DateLayout::Mmddyy => format!("{}{}{}", m, d, yy),
+                                    DateLayout::YyyyMmDd => format!("{:04}-{}-{}", year, m, d),
+                                };
+                                if seen.insert(date.clone()) {
+                                    expected.push(date);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            let dates = Dates::new(
+                first_year,
+                last_year,
+                &layouts,
+                unpadded,
+                all_days_30,
+                &[],
+                &[],
+            );
+            assert_eq!(
+                collect(Box::new(dates.unwrap().generator().unwrap())),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Bytes a candidate is turned into before being tested, see [`crate::Cracker::set_encodings`].
+///
+/// Archivers hash the password in the encoding of the system they run on,
+/// e.g. GBK on Chinese Windows or decomposed UTF-8 on macOS.
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Display, FromStr, Serialize, Deserialize,
+)]
+pub enum PasswordEncoding {
+    #[default]
+    #[display("utf-8")]
+    Utf8,
+    /// UTF-8 in Unicode normalization form C (composed)
+    #[display("nfc")]
+    Nfc,
+    /// UTF-8 in Unicode normalization form D (decomposed), used by macOS
+    #[display("nfd")]
+    Nfd,
+    /// DOS code page of the original ZIP specification
+    #[display("cp437")]
+    Cp437,
+    /// GBK, code page of Simplified Chinese Windows
+    #[display("cp936")]
+    Cp936,
+}
+impl PasswordEncoding {
+    pub fn to_string() -> String {
+        format!(
+            "{},{},{},{},{}",
+            PasswordEncoding::Utf8,
+            PasswordEncoding::Nfc,
+            PasswordEncoding::Nfd,
+            PasswordEncoding::Cp437,
+            PasswordEncoding::Cp936
+        )
+    }
+    /// Whether the password stays UTF-8 in this encoding.
+    pub fn is_utf8(self) -> bool {
+        matches!(
+            self,
+            PasswordEncoding::Utf8 | PasswordEncoding::Nfc | PasswordEncoding::Nfd
+        )
+    }
+    /// Replaces the content of `buffer` with `password` in this encoding,
+    /// returns `false` if a character of `password` has no representation in it.
+    pub fn encode_into(self, password: &str, buffer: &mut Vec<u8>) -> bool {
+        buffer.clear();
+        match self {
+            PasswordEncoding::Utf8 => buffer.extend_from_slice(password.as_bytes()),
+            PasswordEncoding::Nfc => push_chars(password.nfc(), buffer),
+            PasswordEncoding::Nfd => push_chars(password.nfd(), buffer),
+            PasswordEncoding::Cp437 => {
+                for c in password.chars() {
+                    match oem_cp::encode_char_checked(c, &oem_cp::code_table::ENCODING_TABLE_CP437) {
+                        Some(b) => buffer.push(b),
+                        None => return false,
+                    }
+                }
+            }
+            PasswordEncoding::Cp936 => {
+                let mut encoder = encoding_rs::GBK.new_encoder();
+                buffer.reserve(password.len());
+                let (result, _) =
+                    encoder.encode_from_utf8_to_vec_without_replacement(password, buffer, true);
+                return matches!(result, encoding_rs::EncoderResult::InputEmpty);
+            }
+        }
+        true
+    }
+}
+
+fn push_chars(chars: impl Iterator<Item = char>, buffer: &mut Vec<u8>) {
+    let mut utf8 = [0u8; 4];
+    for c in chars {
+        buffer.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+    }
+}
+
+/// Tests a candidate in every encoding of a search, reusing its buffers.
+pub struct Encoder {
+    encodings: Vec<PasswordEncoding>,
+    /// one buffer per encoding, to skip variants identical to an earlier one
+    variants: Vec<Vec<u8>>,
+}
+impl Encoder {
+    pub fn new(encodings: Vec<PasswordEncoding>) -> Self {
+        let variants = vec![Vec::new(); encodings.len()];
+        Encoder {
+            encodings,
+            variants,
+        }
+    }
+    /// Calls `verify` with every distinct encoding of `password` until it returns `true`,
+    /// and returns the encoding which matched.
+    ///
+    /// ASCII is the same in every encoding and is tested once, as the first encoding.
+    /// Candidates which are not UTF-8, such as raw dictionary lines, are tested verbatim
+    /// if UTF-8 is one of the encodings and skipped otherwise.
+    pub fn find<E>(
+        &mut self,
+        password: &[u8],
+        mut verify: impl FnMut(&[u8]) -> Result<bool, E>,
+    ) -> Result<Option<PasswordEncoding>, E> {
+        let first = self.encodings.first().copied().unwrap_or_default();
+        if password.is_ascii() || matches!(self.encodings[..], [] | [PasswordEncoding::Utf8]) {
+            return Ok(verify(password)?.then_some(first));
+        }
+        let password = match std::str::from_utf8(password) {
+            Ok(password) => password,
+            Err(_) if self.encodings.contains(&PasswordEncoding::Utf8) => {
+                return Ok(verify(password)?.then_some(PasswordEncoding::Utf8))
+            }
+            Err(_) => return Ok(None),
+        };
+        for i in 0..self.encodings.len() {
+            let (tried, rest) = self.variants.split_at_mut(i);
+            let variant = &mut rest[0];
+            if !self.encodings[i].encode_into(password, variant)
+                || tried.iter().any(|t| t == variant)
+            {
+                // keep the buffer out of the comparisons of the next encodings
+                variant.clear();
+                continue;
+            }
+            if verify(variant)? {
+                return Ok(Some(self.encodings[i]));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Encoder, PasswordEncoding};
+
+    #[test]
+    fn encode() {
+        let mut buffer = Vec::new();
+        assert!(PasswordEncoding::Cp936.encode_into("中文", &mut buffer));
+        assert_eq!(buffer, vec![0xd6, 0xd0, 0xce, 0xc4]);
+        assert!(PasswordEncoding::Cp437.encode_into("é", &mut buffer));
+        assert_eq!(buffer, vec![0x82]);
+        assert!(!PasswordEncoding::Cp437.encode_into("中", &mut buffer));
+        assert!(PasswordEncoding::Nfd.encode_into("é", &mut buffer));
+        assert_eq!(buffer, "e\u{301}".as_bytes());
+        assert!(PasswordEncoding::Nfc.encode_into("e\u{301}", &mut buffer));
+        assert_eq!(buffer, "é".as_bytes());
+        assert_eq!("cp936".parse(), Ok(PasswordEncoding::Cp936));
+    }
+
+    #[test]
+    fn find_matching_encoding() {
+        let mut encoder = Encoder::new(vec![
+            PasswordEncoding::Utf8,
+            PasswordEncoding::Nfc,
+            PasswordEncoding::Cp437,
+            PasswordEncoding::Cp936,
+        ]);
+        let mut tried = vec![];
+        let found = encoder.find("中文".as_bytes(), |variant| {
+            tried.push(variant.to_vec());
+            Ok::<_, ()>(variant == [0xd6, 0xd0, 0xce, 0xc4])
+        });
+        assert_eq!(found, Ok(Some(PasswordEncoding::Cp936)));
+        // NFC is identical to UTF-8 and CP437 cannot encode it
+        assert_eq!(tried, vec!["中文".as_bytes().to_vec(), vec![0xd6, 0xd0, 0xce, 0xc4]]);
+
+        let mut count = 0;
+        let found = encoder.find(b"ascii", |_| {
+            count += 1;
+            Ok::<_, ()>(false)
+        });
+        assert_eq!((found, count), (Ok(None), 1));
+    }
+
+    #[test]
+    fn single_encoding() {
+        let mut encoder = Encoder::new(vec![PasswordEncoding::Cp936]);
+        let mut tried = vec![];
+        let found = encoder.find("中文".as_bytes(), |variant| {
+            tried.push(variant.to_vec());
+            Ok::<_, ()>(variant == [0xd6, 0xd0, 0xce, 0xc4])
+        });
+        assert_eq!(found, Ok(Some(PasswordEncoding::Cp936)));
+        assert_eq!(tried, vec![vec![0xd6, 0xd0, 0xce, 0xc4]]);
+    }
+    #[test]
+    fn not_utf8() {
+        let gbk = [0xd6, 0xd0, 0xce, 0xc4];
+        let mut encoder = Encoder::new(vec![PasswordEncoding::Cp936]);
+        let mut count = 0;
+        let found = encoder.find(&gbk, |_| {
+            count += 1;
+            Ok::<_, ()>(true)
+        });
+        assert_eq!((found, count), (Ok(None), 0));
+
+        let mut encoder = Encoder::new(vec![PasswordEncoding::Cp936, PasswordEncoding::Utf8]);
+        let mut tried = vec![];
+        let found = encoder.find(&gbk, |variant| {
+            tried.push(variant.to_vec());
+            Ok::<_, ()>(true)
+        });
+        assert_eq!(found, Ok(Some(PasswordEncoding::Utf8)));
+        assert_eq!(tried, vec![gbk.to_vec()]);
+    }
+}
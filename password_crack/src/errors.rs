@@ -1,5 +1,7 @@
 use thiserror::Error;
 use zip::result::ZipError;
+
+use crate::encodings::PasswordEncoding;
 #[derive(Error, Debug)]
 pub enum Errors {
     #[error("standard I/O error - {e}")]
@@ -8,11 +10,70 @@ pub enum Errors {
     InvalidZip { message: String },
     #[error("{message}")]
     MathError { message: String },
+    #[error("the file is not encrypted")]
+    NotEncrypted,
+    #[error("corrupt archive - {message}")]
+    CorruptArchive { message: String },
+    #[error("a worker thread panicked")]
+    WorkerPanicked,
+    #[error("invalid mask - {message}")]
+    InvalidMask { message: String },
+    #[error("invalid rule - {message}")]
+    InvalidRule { message: String },
+    #[error("invalid template - {message}")]
+    InvalidTemplate { message: String },
+    #[error("invalid dates - {message}")]
+    InvalidDates { message: String },
+    #[error("invalid fragments - {message}")]
+    InvalidFragments { message: String },
+    #[error("invalid typos - {message}")]
+    InvalidTypos { message: String },
+    #[error("the candidate stream was already read by an earlier search")]
+    StreamConsumed,
+    #[error("Invalid session file error - {message}")]
+    InvalidSession { message: String },
+    #[error("'{mime_type}' files only take UTF-8 passwords, not '{encoding}'")]
+    UnsupportedEncoding {
+        encoding: PasswordEncoding,
+        mime_type: String,
+    },
+    #[error("unsupported file format '{file_type}'{}", feature_hint(.feature))]
+    UnsupportedFormat {
+        file_type: String,
+        feature: Option<&'static str>,
+    },
+}
+fn feature_hint(feature: &Option<&'static str>) -> String {
+    match feature {
+        Some(feature) => format!(" - enable the '{}' cargo feature to support it", feature),
+        None => String::new(),
+    }
 }
 impl Errors {
     pub fn invalid_zip_error(message: String) -> Self {
         Errors::InvalidZip { message }
     }
+    pub fn invalid_session_error(message: String) -> Self {
+        Errors::InvalidSession { message }
+    }
+    pub fn invalid_mask_error(message: String) -> Self {
+        Errors::InvalidMask { message }
+    }
+    pub fn invalid_rule_error(message: String) -> Self {
+        Errors::InvalidRule { message }
+    }
+    pub fn invalid_template_error(message: String) -> Self {
+        Errors::InvalidTemplate { message }
+    }
+    pub fn invalid_dates_error(message: String) -> Self {
+        Errors::InvalidDates { message }
+    }
+    pub fn invalid_fragments_error(message: String) -> Self {
+        Errors::InvalidFragments { message }
+    }
+    pub fn invalid_typos_error(message: String) -> Self {
+        Errors::InvalidTypos { message }
+    }
 }
 impl std::convert::From<std::io::Error> for Errors {
     fn from(e: std::io::Error) -> Self {
@@ -22,8 +83,11 @@ impl std::convert::From<std::io::Error> for Errors {
 
 impl std::convert::From<ZipError> for Errors {
     fn from(e: ZipError) -> Self {
-        Errors::InvalidZip {
-            message: format!("{}", e),
+        match e {
+            ZipError::Io(e) => Errors::StdIoError { e },
+            e => Errors::InvalidZip {
+                message: format!("{}", e),
+            },
         }
     }
 }
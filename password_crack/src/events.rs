@@ -0,0 +1,68 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::encodings::PasswordEncoding;
+
+/// Time between two [`Event::Progress`].
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What happens during a search, see [`crate::Cracker::subscribe`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The target format was detected, sent once before the workers start.
+    TargetInfo {
+        mime_type: String,
+        description: Option<String>,
+    },
+    WorkerStarted {
+        worker: usize,
+    },
+    /// A worker ran out of candidates or was stopped, after testing `tested` candidates.
+    WorkerFinished {
+        worker: usize,
+        tested: u64,
+    },
+    /// Sent every [`PROGRESS_INTERVAL`] while the search runs, and once at the end.
+    Progress { tested: u64, per_second: f64 },
+    /// Sent with every [`Event::Progress`] when the number of dictionary lines is unknown,
+    /// with compressed dictionaries or de-duplication: `read` bytes out of the `size` bytes
+    /// of all dictionary files.
+    DictionaryProgress { read: u64, size: u64 },
+    /// A dictionary file is opened, the `number`-th of `total`.
+    DictionaryStarted {
+        path: PathBuf,
+        number: usize,
+        total: usize,
+    },
+    Found {
+        password: String,
+        encoding: PasswordEncoding,
+    },
+    /// Sent at the end of a dictionary search: `transcoded` lines were converted from the
+    /// dictionary encoding, `invalid` lines were not valid in it and were tested as raw bytes.
+    DictionaryLines { transcoded: u64, invalid: u64 },
+    /// The search failed, [`crate::Cracker::start`] returns the detailed error.
+    Error {
+        message: String,
+    },
+}
+
+pub type Observer = Arc<dyn Fn(&Event) + Send + Sync>;
+pub type EventReceiver = crossbeam_channel::Receiver<Event>;
+
+/// Forwards events to every observer of a search.
+#[derive(Clone, Default)]
+pub struct EventEmitter {
+    observers: Arc<Vec<Observer>>,
+}
+impl EventEmitter {
+    pub fn new(observers: Vec<Observer>) -> Self {
+        EventEmitter {
+            observers: Arc::new(observers),
+        }
+    }
+    pub fn emit(&self, event: Event) {
+        for observer in self.observers.iter() {
+            observer(&event);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{candidates::Candidates, combinator::WordCase, errors::Errors};
+
+/// Known pieces of a password in an unknown order, e.g. a name, a birth year and `!`.
+///
+/// The candidates are every ordered combination of `min_tokens` to `max_tokens` distinct
+/// tokens, fewest tokens first, each token in every case and every gap filled with every
+/// separator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fragments {
+    /// distinct case variants of every token
+    variants: Vec<Vec<Vec<u8>>>,
+    separators: Vec<Vec<u8>>,
+    min_tokens: usize,
+    max_tokens: usize,
+}
+impl Fragments {
+    /// Fragments of `tokens`, up to all the distinct ones if `max_tokens` is `None`,
+    /// without separator if `separators` is empty and as they are if `cases` is empty.
+    pub fn new(
+        tokens: &[String],
+        min_tokens: usize,
+        max_tokens: Option<usize>,
+        separators: &[String],
+        cases: &[WordCase],
+    ) -> Result<Self, Errors> {
+        let mut distinct: Vec<&String> = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            if !distinct.contains(&token) {
+                distinct.push(token);
+            }
+        }
+        if distinct.is_empty() {
+            return Err(Errors::invalid_fragments_error("no token".to_string()));
+        }
+        let max_tokens = max_tokens.unwrap_or(distinct.len());
+        if min_tokens == 0 || min_tokens > max_tokens || max_tokens > distinct.len() {
+            return Err(Errors::invalid_fragments_error(format!(
+                "the numbers of tokens must be between 1 and {}, the minimum first",
+                distinct.len()
+            )));
+        }
+        let cases = if cases.is_empty() {
+            &[WordCase::AsIs][..]
+        } else {
+            cases
+        };
+        let variants = distinct
+            .iter()
+            .map(|token| {
+                let mut variants: Vec<Vec<u8>> = Vec::with_capacity(cases.len());
+                for case in cases {
+                    let mut variant = Vec::with_capacity(token.len());
+                    case.push(token.as_bytes(), &mut variant);
+                    if !variants.contains(&variant) {
+                        variants.push(variant);
+                    }
+                }
+                variants
+            })
+            .collect();
+        let separators = if separators.is_empty() {
+            vec![Vec::new()]
+        } else {
+            separators.iter().map(|s| s.as_bytes().to_vec()).collect()
+        };
+        Ok(Fragments {
+            variants,
+            separators,
+            min_tokens,
+            max_tokens,
+        })
+    }
+
+    pub fn count(&self) -> Result<usize, Errors> {
+        let overflow = || Errors::MathError {
+            message: String::from("算术溢出"),
+        };
+        // sums[k]: sum over the sets of k tokens of the product of their variant counts
+        let mut sums: Vec<usize> = vec![0; self.max_tokens + 1];
+        sums[0] = 1;
+        for variants in &self.variants {
+            for k in (1..=self.max_tokens).rev() {
+                let added = sums[k - 1]
+                    .checked_mul(variants.len())
+                    .ok_or_else(overflow)?;
+                sums[k] = sums[k].checked_add(added).ok_or_else(overflow)?;
+            }
+        }
+        let mut total: usize = 0;
+        // k! orders of every set of k tokens, k - 1 gaps
+        let mut orders: usize = 1;
+        let mut gaps: usize = 1;
+        for (k, sum) in sums.iter().enumerate().skip(1) {
+            orders = orders.checked_mul(k).ok_or_else(overflow)?;
+            if k > 1 {
+                gaps = gaps
+                    .checked_mul(self.separators.len())
+                    .ok_or_else(overflow)?;
+            }
+            if k >= self.min_tokens {
+                let count = sum
+                    .checked_mul(orders)
+                    .and_then(|c| c.checked_mul(gaps))
+                    .ok_or_else(overflow)?;
+                total = total.checked_add(count).ok_or_else(overflow)?;
+            }
+        }
+        Ok(total)
+    }
+
+    pub fn candidates(self) -> FragmentCandidates {
+        let mut candidates = FragmentCandidates {
+            arrangement: (0..self.min_tokens).collect(),
+            used: vec![false; self.variants.len()],
+            cases: vec![],
+            gaps: vec![],
+            block: 0,
+            next: 0,
+            exhausted: false,
+            fragments: self,
+        };
+        candidates.start_arrangement();
+        candidates
+    }
+}
+
+/// Candidates of [`Fragments`]. The tokens of every arrangement are combined with all their
+/// variants and separators before moving to the next arrangement.
+pub struct FragmentCandidates {
+    fragments: Fragments,
+    /// indices of the tokens of the current candidates, in order
+    arrangement: Vec<usize>,
+    used: Vec<bool>,
+    /// variant of every token and separator of every gap of the next candidate
+    cases: Vec<usize>,
+    gaps: Vec<usize>,
+    /// number of candidates of the current arrangement
+    block: usize,
+    /// index of the next candidate in the current arrangement
+    next: usize,
+    exhausted: bool,
+}
+impl FragmentCandidates {
+    fn start_arrangement(&mut self) {
+        let separators = self.fragments.separators.len();
+        self.block = self.arrangement.iter().fold(
+            separators.saturating_pow(self.arrangement.len() as u32 - 1),
+            |block, &t| block.saturating_mul(self.fragments.variants[t].len()),
+        );
+        self.next = 0;
+    }
+
+    /// Moves to the next arrangement of the same number of tokens in lexicographic order,
+    /// or to the first one with one more token, returns `false` after the last one.
+    fn next_arrangement(&mut self) -> bool {
+        if self.exhausted {
+            return false;
+        }
+        let token_count = self.fragments.variants.len();
+        let len = self.arrangement.len();
+        self.used.iter_mut().for_each(|used| *used = false);
+        for &t in &self.arrangement {
+            self.used[t] = true;
+        }
+        let mut moved = false;
+        for i in (0..len).rev() {
+            self.used[self.arrangement[i]] = false;
+            if let Some(next) = (self.arrangement[i] + 1..token_count).find(|&t| !self.used[t]) {
+                self.arrangement[i] = next;
+                self.used[next] = true;
+                // the smallest unused tokens for the positions after it
+                let mut free = (0..token_count).filter(|&t| !self.used[t]);
+                for slot in i + 1..len {
+                    self.arrangement[slot] = free.next().expect("enough tokens");
+                }
+                moved = true;
+                break;
+            }
+        }
+        if !moved {
+            if len == self.fragments.max_tokens {
+                self.exhausted = true;
+                return false;
+            }
+            self.arrangement = (0..len + 1).collect();
+        }
+        self.start_arrangement();
+        true
+    }
+}
+impl Candidates for FragmentCandidates {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next == self.block && !self.next_arrangement() {
+            return Ok(false);
+        }
+        // the variant of the last token changes first, then the other variants,
+        // then the separators
+        let mut offset = self.next;
+        self.cases.clear();
+        for &t in self.arrangement.iter().rev() {
+            let variants = self.fragments.variants[t].len();
+            self.cases.push(offset % variants);
+            offset /= variants;
+        }
+        self.gaps.clear();
+        for _ in 1..self.arrangement.len() {
+            let separators = self.fragments.separators.len();
+            self.gaps.push(offset % separators);
+            offset /= separators;
+        }
+        buffer.clear();
+        for (i, &t) in self.arrangement.iter().enumerate() {
+            if i > 0 {
+                let gap = self.gaps[self.gaps.len() - i];
+                buffer.extend_from_slice(&self.fragments.separators[gap]);
+            }
+            let case = self.cases[self.cases.len() - 1 - i];
+            buffer.extend_from_slice(&self.fragments.variants[t][case]);
+        }
+        self.next += 1;
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        let mut n = n;
+        loop {
+            let left = self.block - self.next;
+            if n <= left {
+                self.next += n;
+                return Ok(true);
+            }
+            // skip whole arrangements without building their candidates
+            n -= left;
+            self.next = self.block;
+            if !self.next_arrangement() {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Fragments;
+    use crate::{
+        candidates::test::{assert_shards_match, collect},
+        combinator::WordCase,
+    };
+
+    fn tokens(tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn arrangements() {
+        let fragments = Fragments::new(&tokens(&["a", "b", "c", "a"]), 2, None, &[], &[]).unwrap();
+        assert_eq!(fragments.count().unwrap(), 6 + 6);
+        let all = collect(Box::new(fragments.candidates()));
+        assert_eq!(
+            all,
+            vec!["ab", "ac", "ba", "bc", "ca", "cb", "abc", "acb", "bac", "bca", "cab", "cba"]
+        );
+        assert!(Fragments::new(&tokens(&["a", "b"]), 1, Some(3), &[], &[]).is_err());
+        assert!(Fragments::new(&[], 1, None, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn separators_and_cases() {
+        let fragments = Fragments::new(
+            &tokens(&["zhang", "1990"]),
+            1,
+            Some(2),
+            &tokens(&["", "_"]),
+            &[WordCase::AsIs, WordCase::Capitalize],
+        )
+        .unwrap();
+        // "1990" has a single variant
+        assert_eq!(fragments.count().unwrap(), 3 + 2 * 2 * 2);
+        let all = collect(Box::new(fragments.clone().candidates()));
+        assert_eq!(
+            all,
+            vec![
+                "zhang",
+                "Zhang",
+                "1990",
+                "zhang1990",
+                "Zhang1990",
+                "zhang_1990",
+                "Zhang_1990",
+                "1990zhang",
+                "1990Zhang",
+                "1990_zhang",
+                "1990_Zhang"
+            ]
+        );
+        assert_shards_match(|| Box::new(fragments.clone().candidates()), 4);
+    }
+}
@@ -1,77 +1,198 @@
+mod candidates;
 mod charsets;
+mod combinator;
+mod control;
+mod dates;
+mod encodings;
 mod errors;
+mod events;
+mod fragments;
+mod mask;
 mod password_finder;
 mod password_gen;
 mod password_reader;
 mod password_worker;
+mod progress;
+mod rules;
+mod session;
+mod template;
+mod typos;
 #[cfg(feature = "pdf")]
 mod pdf;
 #[cfg(feature = "rar")]
 mod rar;
 #[cfg(feature = "7z")]
 mod seven_z;
+mod verifier;
 mod zip;
 
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
+pub use candidates::Candidates;
 pub use charsets::{charset_lowercase_letters, CharsetChoice};
+pub use combinator::WordCase;
+pub use control::{CancelToken, PauseToken};
+pub use dates::{DateLayout, Dates};
+pub use encodings::PasswordEncoding;
 pub use errors::Errors;
-pub use password_finder::Strategy;
-use password_finder::{get_password_count, password_finder};
+pub use events::{Event, EventReceiver, PROGRESS_INTERVAL};
+use events::{EventEmitter, Observer};
+pub use fragments::Fragments;
+pub use mask::{Mask, MaskGenerator};
+pub use password_finder::{Outcome, Strategy};
+use password_finder::{get_password_count, password_finder, CachedStrategy};
 pub use password_gen::PasswordGenerator;
+pub use password_reader::{CandidateStream, DictEncoding};
+use progress::WorkerPositions;
+pub use rules::{Rule, Rules};
+pub use session::Session;
+pub use template::{Template, TemplateGenerator};
+pub use typos::Typos;
+pub use verifier::{Verifier, VerifierRegistry};
 
-type Passwords = Box<dyn Iterator<Item = String> + Send>;
-fn filter_for_worker_index(
-    passwords: Passwords,
-    worker_count: usize,
-    worker_index: usize,
-) -> Passwords {
-    if worker_count > 1 {
-        Box::new(passwords.enumerate().filter_map(move |(index, password)| {
-            if index % worker_count == worker_index - 1 {
-                Some(password)
-            } else {
-                None
-            }
-        }))
-    } else {
-        passwords
-    }
-}
 #[derive(Clone)]
 pub struct Cracker {
     file_path: String,
     workers: usize,
-    strategy: Strategy,
+    strategy: CachedStrategy,
     total_count: Option<usize>,
-    tested_count: Arc<AtomicU64>,
+    registry: VerifierRegistry,
+    cancel_token: CancelToken,
+    pause_token: PauseToken,
+    positions: Arc<WorkerPositions>,
+    encodings: Vec<PasswordEncoding>,
+    file_hash: Arc<Mutex<Option<String>>>,
+    observers: Vec<Observer>,
 }
 impl Cracker {
     pub fn new(file_path: String, workers: usize, strategy: Strategy) -> Self {
         Cracker {
             file_path,
             workers,
-            strategy,
+            strategy: CachedStrategy::new(strategy),
             total_count: None,
-            tested_count: Arc::new(AtomicU64::new(0)),
+            registry: VerifierRegistry::default(),
+            cancel_token: CancelToken::new(),
+            pause_token: PauseToken::new(),
+            positions: Arc::new(WorkerPositions::new(workers)),
+            encodings: vec![PasswordEncoding::Utf8],
+            file_hash: Arc::new(Mutex::new(None)),
+            observers: vec![],
         }
     }
-    pub fn start(self: &Self) -> Result<Option<String>, Errors> {
-        password_finder(
+    /// Continues the search saved in `session`, see [`Cracker::checkpoint`].
+    pub fn restore(session: Session) -> Result<Self, Errors> {
+        let file_hash = session::file_hash(Path::new(&session.file_path))?;
+        if file_hash != session.file_hash {
+            return Err(Errors::invalid_session_error(format!(
+                "{} changed since the session was saved",
+                session.file_path
+            )));
+        }
+        let mut cracker = Cracker::new(
+            session.file_path.clone(),
+            session.workers,
+            session.strategy.clone(),
+        );
+        cracker.positions = Arc::new(WorkerPositions::from_positions(&session.positions));
+        cracker.encodings = session.encodings;
+        cracker.file_hash = Arc::new(Mutex::new(Some(file_hash)));
+        Ok(cracker)
+    }
+    /// Adds support for another file format, see [`Verifier`].
+    pub fn register_verifier<V: Verifier + 'static>(&mut self, mime_type: &str) -> &mut Self {
+        self.registry.register::<V>(mime_type);
+        self
+    }
+    /// Tests every candidate in each of `encodings`, in order. Defaults to UTF-8 only.
+    /// CP437 and CP936 are rejected by the formats whose verifier does not take raw bytes,
+    /// such as RAR and 7z.
+    pub fn set_encodings(&mut self, encodings: Vec<PasswordEncoding>) -> &mut Self {
+        self.encodings = encodings;
+        self
+    }
+    /// Calls `observer` for every [`Event`] of the searches started afterwards.
+    /// It runs on the search threads and should return quickly.
+    pub fn subscribe(
+        &mut self,
+        observer: impl Fn(&Event) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+    /// Channel receiving every [`Event`] of the searches started afterwards.
+    pub fn events(&mut self) -> EventReceiver {
+        let (send_event, receive_event) = crossbeam_channel::unbounded();
+        self.subscribe(move |event| {
+            let _ = send_event.send(event.clone());
+        });
+        receive_event
+    }
+    pub fn start(self: &Self) -> Result<Outcome, Errors> {
+        let emitter = EventEmitter::new(self.observers.clone());
+        let res = password_finder(
             &self.file_path,
             self.workers,
             self.strategy.clone(),
-            self.tested_count.clone(),
-        )
+            &self.registry,
+            &self.cancel_token,
+            &self.pause_token,
+            self.positions.clone(),
+            self.encodings.clone(),
+            &emitter,
+        );
+        if let Err(e) = &res {
+            emitter.emit(Event::Error {
+                message: e.to_string(),
+            });
+        }
+        res
+    }
+    /// Snapshot of the search progress, save it to continue later with [`Cracker::restore`].
+    pub fn checkpoint(self: &Self) -> Result<Session, Errors> {
+        let mut file_hash = self.file_hash.lock().unwrap();
+        if file_hash.is_none() {
+            *file_hash = Some(session::file_hash(Path::new(&self.file_path))?);
+        }
+        Ok(Session::new(
+            self.file_path.clone(),
+            file_hash.clone().unwrap(),
+            self.strategy.strategy.clone(),
+            self.positions.snapshot(),
+            self.encodings.clone(),
+        ))
+    }
+    /// Stops the search, [`Cracker::start`] then returns [`Outcome::Cancelled`] unless every
+    /// candidate was already tested.
+    /// A cancelled cracker cannot be started again.
+    pub fn cancel(self: &Self) {
+        self.cancel_token.cancel();
+    }
+    pub fn cancel_token(self: &Self) -> CancelToken {
+        self.cancel_token.clone()
+    }
+    /// Parks the workers at their next batch boundary without losing their position.
+    pub fn pause(self: &Self) {
+        self.pause_token.pause();
+    }
+    pub fn resume(self: &Self) {
+        self.pause_token.resume();
+    }
+    pub fn is_paused(self: &Self) -> bool {
+        self.pause_token.is_paused()
+    }
+    pub fn pause_token(self: &Self) -> PauseToken {
+        self.pause_token.clone()
     }
-    pub fn count(self: &Self) -> Result<usize, Errors> {
+    /// Number of candidates to test, `None` if unknown before reading them all.
+    pub fn count(self: &Self) -> Result<Option<usize>, Errors> {
         match self.total_count {
-            Some(c) => Ok(c),
+            Some(c) => Ok(Some(c)),
             None => get_password_count(&self.strategy),
         }
     }
     pub fn tested_count(self: &Self) -> u64 {
-        self.tested_count.load(Ordering::SeqCst)
+        self.positions.tested_count()
     }
 }
@@ -0,0 +1,391 @@
+use crate::{
+    candidates::{Candidates, Passwords},
+    charsets::CharsetChoice,
+    errors::Errors,
+};
+
+/// Maximum number of custom charsets, referenced as `?1` to `?4`.
+pub const CUSTOM_CHARSETS: usize = 4;
+
+/// Hashcat-style mask: one charset per position of the password.
+///
+/// `?l`, `?u`, `?d` and `?s` are the lower, upper, number and special charsets of
+/// [`CharsetChoice`], `?a` all of them, `?1` to `?4` the custom charsets and `??` a literal `?`.
+/// Any other character stands for itself, e.g. `Admin?d?d?d?d`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    positions: Vec<Vec<char>>,
+}
+impl Mask {
+    /// Custom charsets may themselves use the built-in placeholders, e.g. `?dabc`.
+    pub fn parse(mask: &str, custom_charsets: &[String]) -> Result<Self, Errors> {
+        if custom_charsets.len() > CUSTOM_CHARSETS {
+            return Err(Errors::invalid_mask_error(format!(
+                "at most {} custom charsets are supported",
+                CUSTOM_CHARSETS
+            )));
+        }
+        let custom = custom_charsets
+            .iter()
+            .map(|charset| {
+                let charset: Vec<char> = parse_positions(charset, &[])?.concat();
+                if charset.is_empty() {
+                    return Err(Errors::invalid_mask_error(
+                        "custom charsets cannot be empty".to_string(),
+                    ));
+                }
+                Ok(unique(charset))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let positions = parse_positions(mask, &custom)?;
+        if positions.is_empty() {
+            return Err(Errors::invalid_mask_error("the mask is empty".to_string()));
+        }
+        Ok(Mask { positions })
+    }
+
+    /// Number of positions, which is the length of the longest password.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Always `false`, parsing rejects empty masks.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Charset of every position.
+    pub(crate) fn positions(&self) -> &[Vec<char>] {
+        &self.positions
+    }
+
+    /// Number of passwords matching the mask, or one of its prefixes with `increment`.
+    pub fn count(&self, increment: bool) -> Result<usize, Errors> {
+        let overflow = || Errors::MathError {
+            message: String::from("算术溢出"),
+        };
+        let mut total: usize = 0;
+        let mut len_count: usize = 1;
+        for (i, charset) in self.positions.iter().enumerate() {
+            len_count = len_count.checked_mul(charset.len()).ok_or_else(overflow)?;
+            if increment || i + 1 == self.len() {
+                total = total.checked_add(len_count).ok_or_else(overflow)?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Generator of the passwords matching the mask, shortest first with `increment`.
+    pub fn generator(&self, increment: bool) -> Result<MaskGenerator, Errors> {
+        Ok(MaskGenerator {
+            positions: self
+                .positions
+                .iter()
+                .map(|charset| charset.iter().map(|c| c.to_string().into_bytes()).collect())
+                .collect(),
+            min_len: if increment { 1 } else { self.len() },
+            digits: vec![0; if increment { 1 } else { self.len() }],
+            generated_count: 0,
+            total_to_generate: self.count(increment)?,
+        })
+    }
+}
+
+fn parse_positions(mask: &str, custom: &[Vec<char>]) -> Result<Vec<Vec<char>>, Errors> {
+    let mut positions = vec![];
+    let mut chars = mask.chars();
+    while let Some(c) = chars.next() {
+        if c != '?' {
+            positions.push(vec![c]);
+            continue;
+        }
+        let charset = match chars.next() {
+            Some('l') => CharsetChoice::Lower.to_charset(),
+            Some('u') => CharsetChoice::Upper.to_charset(),
+            Some('d') => CharsetChoice::Number.to_charset(),
+            Some('s') => CharsetChoice::Special.to_charset(),
+            Some('a') => [
+                CharsetChoice::Lower,
+                CharsetChoice::Upper,
+                CharsetChoice::Number,
+                CharsetChoice::Special,
+            ]
+            .iter()
+            .flat_map(|choice| choice.to_charset())
+            .collect(),
+            Some('?') => vec!['?'],
+            Some(n @ '1'..='4') => {
+                let i = n as usize - '1' as usize;
+                match custom.get(i) {
+                    Some(charset) => charset.clone(),
+                    None => {
+                        return Err(Errors::invalid_mask_error(format!(
+                            "custom charset ?{} is not defined",
+                            n
+                        )))
+                    }
+                }
+            }
+            Some(other) => {
+                return Err(Errors::invalid_mask_error(format!(
+                    "unknown placeholder ?{}",
+                    other
+                )))
+            }
+            None => {
+                return Err(Errors::invalid_mask_error(
+                    "the mask ends with a single '?', use '??' for a literal one".to_string(),
+                ))
+            }
+        };
+        positions.push(charset);
+    }
+    Ok(positions)
+}
+
+fn unique(charset: Vec<char>) -> Vec<char> {
+    let mut unique = Vec::with_capacity(charset.len());
+    for c in charset {
+        if !unique.contains(&c) {
+            unique.push(c);
+        }
+    }
+    unique
+}
+
+/// Passwords matching a [`Mask`], the last position changes fastest.
+///
+/// Like [`crate::PasswordGenerator`], the candidates form a mixed-radix sequence so that
+/// workers skip the candidates of the others without generating them.
+pub struct MaskGenerator {
+    /// UTF-8 encoding of every char of the charset of every position
+    positions: Vec<Vec<Vec<u8>>>,
+    min_len: usize,
+    /// charset index of every character of the next candidate
+    digits: Vec<usize>,
+    generated_count: usize,
+    total_to_generate: usize,
+}
+impl Candidates for MaskGenerator {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.generated_count >= self.total_to_generate {
+            return Ok(false);
+        }
+        buffer.clear();
+        for (charset, &d) in self.positions.iter().zip(&self.digits) {
+            buffer.extend_from_slice(&charset[d]);
+        }
+        self.advance(1);
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        self.advance(n);
+        Ok(self.generated_count < self.total_to_generate)
+    }
+}
+impl MaskGenerator {
+    /// Positions the generator so that the next candidate is the one at `index`.
+    pub fn seek(&mut self, index: usize) {
+        if index >= self.total_to_generate {
+            self.generated_count = self.total_to_generate;
+            return;
+        }
+        let mut len = self.min_len;
+        let mut offset = index;
+        // `total_to_generate` fits in a usize, so does every length count below it
+        let mut len_count: usize = self.positions[..len].iter().map(Vec::len).product();
+        while offset >= len_count {
+            offset -= len_count;
+            len_count *= self.positions[len].len();
+            len += 1;
+        }
+        self.digits = vec![0; len];
+        for (digit, charset) in self.digits.iter_mut().zip(&self.positions).rev() {
+            *digit = offset % charset.len();
+            offset /= charset.len();
+        }
+        self.generated_count = index;
+    }
+
+    /// Moves `n` candidates forward.
+    fn advance(&mut self, n: usize) {
+        let target = self.generated_count.saturating_add(n);
+        if target >= self.total_to_generate {
+            self.generated_count = self.total_to_generate;
+            return;
+        }
+        // add `n` to the digits, from the last one
+        let mut carry = n;
+        for (digit, charset) in self.digits.iter_mut().zip(&self.positions).rev() {
+            if carry == 0 {
+                break;
+            }
+            let base = charset.len();
+            let sum = *digit + carry % base;
+            *digit = sum % base;
+            carry = carry / base + sum / base;
+        }
+        if carry == 0 {
+            self.generated_count = target;
+        } else {
+            // overflowed into longer passwords
+            self.seek(target);
+        }
+    }
+}
+
+/// Every word of `words` combined with every password of a mask, as in `zhangsan1990`.
+/// The mask passwords of a word come one after the other.
+pub struct HybridCandidates {
+    words: Passwords,
+    mask: MaskGenerator,
+    mask_count: usize,
+    mask_first: bool,
+    word: Vec<u8>,
+    part: Vec<u8>,
+    /// index of the next mask password, `mask_count` to read the next word first
+    next: usize,
+}
+impl HybridCandidates {
+    pub fn new(words: Passwords, mask: MaskGenerator, mask_first: bool) -> Self {
+        let mask_count = mask.total_to_generate;
+        HybridCandidates {
+            words,
+            mask,
+            mask_count,
+            mask_first,
+            word: Vec::new(),
+            part: Vec::new(),
+            next: mask_count,
+        }
+    }
+}
+impl Candidates for HybridCandidates {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next == self.mask_count {
+            if !self.words.next_into(&mut self.word)? {
+                return Ok(false);
+            }
+            self.mask.seek(0);
+            self.next = 0;
+        }
+        self.mask.next_into(&mut self.part)?;
+        self.next += 1;
+        buffer.clear();
+        if self.mask_first {
+            buffer.extend_from_slice(&self.part);
+            buffer.extend_from_slice(&self.word);
+        } else {
+            buffer.extend_from_slice(&self.word);
+            buffer.extend_from_slice(&self.part);
+        }
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        let left = self.mask_count - self.next;
+        if n <= left {
+            self.mask.advance_by(n)?;
+            self.next += n;
+            return Ok(true);
+        }
+        // skip whole words without generating their mask passwords
+        let n = n - left;
+        self.next = self.mask_count;
+        if !self.words.advance_by(n / self.mask_count)? {
+            return Ok(false);
+        }
+        if n % self.mask_count > 0 {
+            if !self.words.next_into(&mut self.word)? {
+                return Ok(false);
+            }
+            self.next = n % self.mask_count;
+            self.mask.seek(self.next);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HybridCandidates, Mask};
+    use crate::{
+        candidates::test::{assert_shards_match, collect, words},
+        Errors,
+    };
+
+    #[test]
+    fn parse_and_count() {
+        let mask = Mask::parse("Admin?d?d?d?d", &[]).unwrap();
+        assert_eq!(mask.len(), 9);
+        assert_eq!(mask.count(false).unwrap(), 10_000);
+        let mask = Mask::parse("?u?l?l?l?l?d?d", &[]).unwrap();
+        assert_eq!(mask.count(false).unwrap(), 26usize.pow(5) * 100);
+        assert_eq!(Mask::parse("?a", &[]).unwrap().count(false).unwrap(), 84);
+        let mask = Mask::parse("?1?2??", &["abca".to_string(), "?dx".to_string()]).unwrap();
+        assert_eq!(mask.count(false).unwrap(), 3 * 11);
+        assert_eq!(mask.count(true).unwrap(), 3 + 3 * 11 + 3 * 11);
+
+        for invalid in ["", "?x", "abc?", "?3"] {
+            assert!(matches!(
+                Mask::parse(invalid, &["ab".to_string()]),
+                Err(Errors::InvalidMask { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn generate_in_order() {
+        let mask = Mask::parse("x?1?d", &["ab".to_string()]).unwrap();
+        let generated = collect(Box::new(mask.generator(false).unwrap()));
+        assert_eq!(generated.len(), 20);
+        assert_eq!(&generated[..3], ["xa0", "xa1", "xa2"]);
+        assert_eq!(generated[10], "xb0");
+
+        let generated = collect(Box::new(mask.generator(true).unwrap()));
+        assert_eq!(&generated[..4], ["x", "xa", "xb", "xa0"]);
+        assert_eq!(generated.len(), mask.count(true).unwrap());
+    }
+
+    #[test]
+    fn shards_cover_keyspace_once() {
+        let mask = Mask::parse("?1?d?1", &["abc".to_string()]).unwrap();
+        let expected = collect(Box::new(mask.generator(true).unwrap()));
+        assert_eq!(expected.len(), 3 + 30 + 90);
+        assert_shards_match(|| Box::new(mask.generator(true).unwrap()), 4);
+    }
+
+    #[test]
+    fn hybrid() {
+        let mask = Mask::parse("?1?d", &["!#".to_string()]).unwrap();
+        let words = || words(&["zhangsan", "Password"]);
+        let generated = collect(Box::new(HybridCandidates::new(
+            words(),
+            mask.generator(false).unwrap(),
+            false,
+        )));
+        assert_eq!(generated.len(), 2 * 20);
+        assert_eq!(&generated[..2], ["zhangsan!0", "zhangsan!1"]);
+        assert_eq!(generated[23], "Password!3");
+        let generated = collect(Box::new(HybridCandidates::new(
+            words(),
+            mask.generator(true).unwrap(),
+            true,
+        )));
+        assert_eq!(&generated[..3], ["!zhangsan", "#zhangsan", "!0zhangsan"]);
+
+        for workers in 1..=7 {
+            assert_shards_match(
+                || {
+                    Box::new(HybridCandidates::new(
+                        words(),
+                        mask.generator(true).unwrap(),
+                        true,
+                    ))
+                },
+                workers,
+            );
+        }
+    }
+}
@@ -1,77 +1,919 @@
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 
+use crate::candidates::Passwords;
+use crate::combinator::{Combinator, CombinatorCandidates, WordCase};
+use crate::control::{CancelToken, PauseToken};
+use crate::dates::{DateLayout, Dates};
+use crate::encodings::PasswordEncoding;
 use crate::errors::Errors;
-use crate::password_finder::Strategy::{GenPasswords, PasswordFile};
+use crate::events::{Event, EventEmitter, PROGRESS_INTERVAL};
+use crate::fragments::Fragments;
+use crate::mask::{HybridCandidates, Mask};
+use crate::password_finder::Strategy::{Dictionaries, GenPasswords, Hybrid, PasswordFile, Stream};
 use crate::password_gen::password_generator_count;
-use crate::password_reader::password_reader_count;
+use crate::password_reader::{
+    CandidateStream, DictEncoding, DictionaryList, DictionaryStats, LineBatch,
+};
+use crate::password_worker::WorkerShared;
+use crate::progress::WorkerPositions;
+use crate::rules::{RuleCandidates, Rules};
+use crate::template::Template;
+use crate::typos::{TypoCandidates, Typos};
+use crate::verifier::VerifierRegistry;
+
+use serde::{Deserialize, Serialize};
 
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
-#[derive(Clone, Debug)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Strategy {
-    PasswordFile(PathBuf),
+    PasswordFile {
+        path: PathBuf,
+        #[serde(default)]
+        encoding: DictEncoding,
+        /// rule files applied to every line, see [`crate::Rules`]
+        #[serde(default)]
+        rules: Vec<PathBuf>,
+    },
     GenPasswords {
         charsets: Vec<char>,
         min_password_len: usize,
         max_password_len: usize,
+        /// index of the first candidate to test, see [`crate::PasswordGenerator::index_of`]
+        #[serde(default)]
+        skip: usize,
+        /// maximum number of candidates to test
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Several dictionary files and directories, read one after the other.
+    Dictionaries {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        encoding: DictEncoding,
+        /// skip the lines already read in an earlier dictionary, or earlier in the same one
+        #[serde(default)]
+        dedup: bool,
+        /// rule files applied to every line, see [`crate::Rules`]
+        #[serde(default)]
+        rules: Vec<PathBuf>,
+    },
+    /// Passwords matching a hashcat-style mask, see [`crate::Mask`].
+    Mask {
+        mask: String,
+        /// charsets of the placeholders `?1` to `?4`
+        #[serde(default)]
+        custom_charsets: Vec<String>,
+        /// also test the passwords matching a prefix of the mask, shortest first
+        #[serde(default)]
+        increment: bool,
+    },
+    /// Every dictionary line combined with every password matching a mask,
+    /// e.g. `zhangsan?d?d?d?d` for `zhangsan1990`.
+    Hybrid {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        encoding: DictEncoding,
+        mask: String,
+        #[serde(default)]
+        custom_charsets: Vec<String>,
+        #[serde(default)]
+        increment: bool,
+        /// put the mask before the line instead of after it
+        #[serde(default)]
+        mask_first: bool,
+    },
+    /// Passwords matching a template of literals, alternatives, ranges and placeholders,
+    /// see [`crate::Template`].
+    Template {
+        template: String,
+        /// charsets of the placeholders `?1` to `?4`
+        #[serde(default)]
+        custom_charsets: Vec<String>,
+    },
+    /// Calendar dates of the years `first_year` to `last_year`, see [`crate::Dates`].
+    Dates {
+        first_year: u32,
+        last_year: u32,
+        #[serde(default = "DateLayout::all")]
+        layouts: Vec<DateLayout>,
+        /// also test the dates without the leading zeros of their month and day
+        #[serde(default)]
+        unpadded: bool,
+        /// also test days 29 and 30 of every month, e.g. February 30th
+        #[serde(default)]
+        all_days_30: bool,
+        #[serde(default)]
+        prefixes: Vec<String>,
+        #[serde(default)]
+        suffixes: Vec<String>,
+    },
+    /// Every line of the `left` dictionary joined to every line of the `right` one,
+    /// e.g. a list of first names with a list of suffixes. The right dictionary is loaded
+    /// in memory.
+    Combinator {
+        left: PathBuf,
+        right: PathBuf,
+        #[serde(default)]
+        encoding: DictEncoding,
+        /// put between the two words
+        #[serde(default)]
+        separator: String,
+        /// every pair is tested in each of these cases, as-is if empty
+        #[serde(default)]
+        cases: Vec<WordCase>,
+    },
+    /// Ordered combinations of distinct known pieces of the password, see [`crate::Fragments`].
+    Fragments {
+        tokens: Vec<String>,
+        #[serde(default = "default_min_tokens")]
+        min_tokens: usize,
+        /// every distinct token if `None`
+        #[serde(default)]
+        max_tokens: Option<usize>,
+        /// put between two tokens, nothing if empty
+        #[serde(default)]
+        separators: Vec<String>,
+        /// every token is tested in each of these cases, as-is if empty
+        #[serde(default)]
+        cases: Vec<WordCase>,
+    },
+    /// Variants of a remembered password with up to `max_edits` typos, see [`crate::Typos`].
+    Typos {
+        password: String,
+        #[serde(default = "default_max_edits")]
+        max_edits: usize,
+        /// characters inserted or substituted
+        charset: Vec<char>,
+    },
+    /// Candidates read from a pipe or any other reader, one per line, whose number is unknown.
+    Stream {
+        #[serde(skip, default = "CandidateStream::stdin")]
+        source: CandidateStream,
+        #[serde(default)]
+        encoding: DictEncoding,
+        /// rule files applied to every line, see [`crate::Rules`]
+        #[serde(default)]
+        rules: Vec<PathBuf>,
+    },
+}
+impl Strategy {
+    /// Dictionary files of the strategy, `None` if it generates its candidates.
+    pub(crate) fn dictionaries(&self) -> Result<Option<DictionaryList>, Errors> {
+        match self {
+            PasswordFile { path, encoding, .. } => {
+                DictionaryList::new(std::slice::from_ref(path), *encoding, false).map(Some)
+            }
+            Dictionaries {
+                paths,
+                encoding,
+                dedup,
+                ..
+            } => DictionaryList::new(paths, *encoding, *dedup).map(Some),
+            Hybrid {
+                paths, encoding, ..
+            } => DictionaryList::new(paths, *encoding, false).map(Some),
+            Strategy::Combinator { left, encoding, .. } => {
+                DictionaryList::new(std::slice::from_ref(left), *encoding, false).map(Some)
+            }
+            GenPasswords { .. }
+            | Strategy::Mask { .. }
+            | Strategy::Fragments { .. }
+            | Strategy::Typos { .. }
+            | Strategy::Template { .. }
+            | Strategy::Dates { .. }
+            | Stream { .. } => Ok(None),
+        }
+    }
+    /// Candidates derived from every dictionary line, `None` if the lines are tested verbatim.
+    pub(crate) fn line_expansion(&self) -> Result<Option<LineExpansion>, Errors> {
+        match self {
+            PasswordFile { rules, .. } | Dictionaries { rules, .. } | Stream { rules, .. }
+                if !rules.is_empty() =>
+            {
+                Ok(Some(LineExpansion::Rules(Arc::new(Rules::load(rules)?))))
+            }
+            Hybrid {
+                mask,
+                custom_charsets,
+                increment,
+                mask_first,
+                ..
+            } => Ok(Some(LineExpansion::Mask {
+                mask: Mask::parse(mask, custom_charsets)?,
+                increment: *increment,
+                mask_first: *mask_first,
+            })),
+            Strategy::Combinator {
+                right,
+                encoding,
+                separator,
+                cases,
+                ..
+            } => Ok(Some(LineExpansion::Combinator(Arc::new(Combinator::load(
+                right, *encoding, separator, cases,
+            )?)))),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn default_min_tokens() -> usize {
+    1
+}
+
+fn default_max_edits() -> usize {
+    1
+}
+
+/// Candidates derived from every dictionary line. With `n` workers, every worker reads every
+/// line and worker `w` tests the derived candidates `w, w + n...` of the whole search.
+pub(crate) enum LineExpansion {
+    Rules(Arc<Rules>),
+    Mask {
+        mask: Mask,
+        increment: bool,
+        mask_first: bool,
     },
+    Combinator(Arc<Combinator>),
+}
+impl LineExpansion {
+    /// Number of candidates derived from one line.
+    pub(crate) fn count(&self) -> Result<usize, Errors> {
+        match self {
+            LineExpansion::Rules(rules) => Ok(rules.len()),
+            LineExpansion::Mask {
+                mask, increment, ..
+            } => mask.count(*increment),
+            LineExpansion::Combinator(combinator) => Ok(combinator.count()),
+        }
+    }
+    pub(crate) fn candidates(&self, lines: Passwords) -> Result<Passwords, Errors> {
+        Ok(match self {
+            LineExpansion::Rules(rules) => Box::new(RuleCandidates::new(lines, rules.clone())),
+            LineExpansion::Mask {
+                mask,
+                increment,
+                mask_first,
+            } => Box::new(HybridCandidates::new(
+                lines,
+                mask.generator(*increment)?,
+                *mask_first,
+            )),
+            LineExpansion::Combinator(combinator) => {
+                Box::new(CombinatorCandidates::new(lines, combinator.clone()))
+            }
+        })
+    }
+}
+
+/// Candidates of the strategies which keep them all in memory, generated once for the count
+/// and every worker.
+#[derive(Clone)]
+pub(crate) enum CandidateList {
+    Typos(Arc<LineBatch>),
+    Dates(Dates),
+}
+impl CandidateList {
+    pub(crate) fn count(&self) -> Result<usize, Errors> {
+        match self {
+            CandidateList::Typos(variants) => Ok(variants.len()),
+            CandidateList::Dates(dates) => dates.count(),
+        }
+    }
+    pub(crate) fn candidates(&self) -> Result<Passwords, Errors> {
+        Ok(match self {
+            CandidateList::Typos(variants) => Box::new(TypoCandidates::new(variants.clone())),
+            CandidateList::Dates(dates) => Box::new(dates.generator()?),
+        })
+    }
+}
+
+/// A strategy with its [`CandidateList`], generated on first use and shared by its clones.
+#[derive(Clone)]
+pub(crate) struct CachedStrategy {
+    pub(crate) strategy: Strategy,
+    candidate_list: Arc<Mutex<Option<CandidateList>>>,
+}
+impl CachedStrategy {
+    pub(crate) fn new(strategy: Strategy) -> Self {
+        CachedStrategy {
+            strategy,
+            candidate_list: Arc::new(Mutex::new(None)),
+        }
+    }
+    /// Candidates kept in memory, `None` if the strategy generates or reads them on the fly.
+    pub(crate) fn candidate_list(&self) -> Result<Option<CandidateList>, Errors> {
+        let mut list = self.candidate_list.lock().unwrap();
+        if list.is_none() {
+            *list = match &self.strategy {
+                Strategy::Typos {
+                    password,
+                    max_edits,
+                    charset,
+                } => Some(CandidateList::Typos(Arc::new(
+                    Typos::new(password, *max_edits, charset)?.variants(),
+                ))),
+                Strategy::Dates {
+                    first_year,
+                    last_year,
+                    layouts,
+                    unpadded,
+                    all_days_30,
+                    prefixes,
+                    suffixes,
+                } => Some(CandidateList::Dates(Dates::new(
+                    *first_year,
+                    *last_year,
+                    layouts,
+                    *unpadded,
+                    *all_days_30,
+                    prefixes,
+                    suffixes,
+                )?)),
+                _ => None,
+            };
+        }
+        Ok(list.clone())
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// `password` unlocked the target once turned into bytes with `encoding`
+    Found {
+        password: String,
+        encoding: PasswordEncoding,
+    },
+    /// every candidate was tested
+    NotFound,
+    /// the search was stopped through [`CancelToken::cancel`] after testing `tested` candidates
+    Cancelled { tested: u64 },
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn password_finder(
     file_path: &str,
     workers: usize,
-    strategy: Strategy,
-    t: Arc<AtomicU64>,
-) -> Result<Option<String>, Errors>
+    strategy: CachedStrategy,
+    registry: &VerifierRegistry,
+    cancel_token: &CancelToken,
+    pause_token: &PauseToken,
+    positions: Arc<WorkerPositions>,
+    encodings: Vec<PasswordEncoding>,
+    emitter: &EventEmitter,
+) -> Result<Outcome, Errors>
 where
 {
     let file_path = Path::new(file_path);
-    let file_type = infer::get_from_path(&file_path)?;
+    // fail early if no verifier handles this file
+    let mime_type = registry.detect(file_path)?;
     //停止与线程关闭信号量
     let stop_workers_signal = Arc::new(AtomicBool::new(false));
     let stop_gen_signal = Arc::new(AtomicBool::new(false));
-    let (send_found_password, receive_found_password): (Sender<String>, Receiver<String>) =
-        crossbeam_channel::bounded(1);
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let (send_found_password, receive_found_password): (
+        Sender<Result<(String, PasswordEncoding), Errors>>,
+        Receiver<Result<(String, PasswordEncoding), Errors>>,
+    ) = crossbeam_channel::bounded(1);
+    let dictionaries = strategy.strategy.dictionaries()?;
+    let is_dictionary = dictionaries.is_some() || matches!(strategy.strategy, Stream { .. });
+    // the progress of dictionaries whose lines are not counted is measured in bytes
+    let dictionary_size = match &dictionaries {
+        Some(dictionaries) if !dictionaries.counted()? => Some(dictionaries.size()?),
+        _ => None,
+    };
+    let dictionary_stats = Arc::new(DictionaryStats::default());
+    let shared = WorkerShared {
+        send_password_found: send_found_password,
+        stop_workers_signal: stop_workers_signal.clone(),
+        cancel_token: cancel_token.clone(),
+        interrupted: interrupted.clone(),
+        pause_token: pause_token.clone(),
+        positions: positions.clone(),
+        dictionary_stats: dictionary_stats.clone(),
+        encodings,
+        emitter: emitter.clone(),
+    };
     let worker_handles = crate::password_worker::password_check(
-        workers,
-        file_path,
-        strategy,
-        send_found_password.clone(),
-        stop_workers_signal.clone(),
-        file_type,
-        t,
+        workers, file_path, strategy, &mime_type, registry, &shared,
     )?;
     // drop reference in `main` so that it disappears completely with workers for a clean shutdown
-    drop(send_found_password);
+    drop(shared);
 
-    let res = match receive_found_password.recv() {
-        Ok(password_found) => {
+    let mut last_progress = (Instant::now(), positions.tested_count());
+    let mut emit_progress = || {
+        let tested = positions.tested_count();
+        let elapsed = last_progress.0.elapsed().as_secs_f64();
+        let per_second = (tested.saturating_sub(last_progress.1)) as f64 / elapsed;
+        emitter.emit(Event::Progress { tested, per_second });
+        if let Some(size) = dictionary_size {
+            emitter.emit(Event::DictionaryProgress {
+                read: dictionary_stats.read_bytes(),
+                size,
+            });
+        }
+        last_progress = (Instant::now(), tested);
+    };
+    let received = loop {
+        match receive_found_password.recv_timeout(PROGRESS_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => emit_progress(),
+            Ok(worker_result) => break Some(worker_result),
+            Err(RecvTimeoutError::Disconnected) => break None,
+        }
+    };
+    let res = match received {
+        Some(worker_result) => {
             // stop generating values first to avoid deadlock on channel
             stop_gen_signal.store(true, Ordering::Relaxed);
             // stop workers
             stop_workers_signal.store(true, Ordering::Relaxed);
+            // unblock workers still trying to report a password or a failure
+            drop(receive_found_password);
             for h in worker_handles {
-                h.join().unwrap();
+                h.join().map_err(|_| Errors::WorkerPanicked)?;
             }
-            Some(password_found)
-        }
-        Err(e) => {
-            println!("66666{}", e);
-            None
+            let (password, encoding) = worker_result?;
+            emitter.emit(Event::Found {
+                password: password.clone(),
+                encoding,
+            });
+            Outcome::Found { password, encoding }
         }
+        // all workers are done without finding anything, a cancel coming after the last
+        // candidates does not count
+        None if interrupted.load(Ordering::Relaxed) => Outcome::Cancelled {
+            tested: positions.tested_count(),
+        },
+        None => Outcome::NotFound,
     };
+    if is_dictionary {
+        emitter.emit(Event::DictionaryLines {
+            transcoded: dictionary_stats.transcoded(),
+            invalid: dictionary_stats.invalid(),
+        });
+    }
+    emit_progress();
     // drop(send_progress_info);
     Ok(res)
 }
-pub fn get_password_count(strategy: &Strategy) -> Result<usize, Errors> {
-    let total_password_count = match &strategy {
+/// Number of candidates of `strategy`, `None` if unknown before reading them all.
+pub fn get_password_count(cached: &CachedStrategy) -> Result<Option<usize>, Errors> {
+    let strategy = &cached.strategy;
+    let total_password_count = match strategy {
         GenPasswords {
             charsets,
             min_password_len,
             max_password_len,
-        } => password_generator_count(charsets, *min_password_len, *max_password_len),
-        PasswordFile(password_file_path) => password_reader_count(password_file_path),
+            skip,
+            limit,
+        } => {
+            password_generator_count(charsets, *min_password_len, *max_password_len).map(|count| {
+                let count = count.saturating_sub(*skip);
+                Some(limit.map_or(count, |limit| count.min(limit)))
+            })
+        }
+        PasswordFile { .. } | Dictionaries { .. } | Hybrid { .. } | Strategy::Combinator { .. } => {
+            let lines = match strategy.dictionaries()? {
+                Some(dictionaries) => dictionaries.count()?,
+                None => None,
+            };
+            match (lines, strategy.line_expansion()?) {
+                (Some(lines), Some(expansion)) => lines
+                    .checked_mul(expansion.count()?)
+                    .map(Some)
+                    .ok_or(Errors::MathError {
+                        message: String::from("算术溢出"),
+                    }),
+                (lines, _) => Ok(lines),
+            }
+        }
+        Strategy::Mask {
+            mask,
+            custom_charsets,
+            increment,
+        } => Mask::parse(mask, custom_charsets)?
+            .count(*increment)
+            .map(Some),
+        Strategy::Fragments {
+            tokens,
+            min_tokens,
+            max_tokens,
+            separators,
+            cases,
+        } => Fragments::new(tokens, *min_tokens, *max_tokens, separators, cases)?
+            .count()
+            .map(Some),
+        Strategy::Template {
+            template,
+            custom_charsets,
+        } => Ok(Some(Template::parse(template, custom_charsets)?.count())),
+        Strategy::Typos { .. } | Strategy::Dates { .. } => cached
+            .candidate_list()?
+            .map(|list| list.count())
+            .transpose(),
+        Stream { .. } => Ok(None),
     };
     total_password_count
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Cursor,
+        path::Path,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use super::{get_password_count, CachedStrategy, CandidateList};
+    use crate::{
+        CandidateStream, Cracker, DictEncoding, Errors, Event, Outcome, PasswordEncoding, Strategy,
+        Verifier,
+    };
+
+    static RECORDED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Recorder;
+    impl Verifier for Recorder {
+        fn prepare(_file_path: &Path) -> Result<Self, Errors> {
+            Ok(Recorder)
+        }
+        fn verify(&mut self, password: &[u8]) -> Result<bool, Errors> {
+            let password = String::from_utf8(password.to_vec()).unwrap();
+            RECORDED.lock().unwrap().push(password);
+            Ok(false)
+        }
+        fn batch_size(&self) -> usize {
+            1
+        }
+    }
+
+    struct Never;
+    impl Verifier for Never {
+        fn prepare(_file_path: &Path) -> Result<Self, Errors> {
+            Ok(Never)
+        }
+        fn verify(&mut self, _password: &[u8]) -> Result<bool, Errors> {
+            Ok(false)
+        }
+    }
+
+    struct Secret;
+    impl Verifier for Secret {
+        fn prepare(_file_path: &Path) -> Result<Self, Errors> {
+            Ok(Secret)
+        }
+        fn verify(&mut self, password: &[u8]) -> Result<bool, Errors> {
+            Ok(password == b"secret")
+        }
+    }
+
+    struct Utf8Only;
+    impl Verifier for Utf8Only {
+        fn prepare(_file_path: &Path) -> Result<Self, Errors> {
+            Ok(Utf8Only)
+        }
+        fn verify(&mut self, _password: &[u8]) -> Result<bool, Errors> {
+            Ok(false)
+        }
+        fn takes_raw_bytes(&self) -> bool {
+            false
+        }
+    }
+
+    struct Corrupt;
+    impl Verifier for Corrupt {
+        fn prepare(_file_path: &Path) -> Result<Self, Errors> {
+            Ok(Corrupt)
+        }
+        fn verify(&mut self, _password: &[u8]) -> Result<bool, Errors> {
+            Err(Errors::CorruptArchive {
+                message: "broken".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn worker_failure_stops_search() {
+        let path = std::env::temp_dir().join("password_crack_worker_failure.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a', 'b', 'c'],
+            min_password_len: 1,
+            max_password_len: 4,
+            skip: 0,
+            limit: None,
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 4, strategy);
+        cracker.register_verifier::<Corrupt>("application/pdf");
+        let res = cracker.start();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(res, Err(Errors::CorruptArchive { .. })));
+    }
+
+    #[test]
+    fn byte_encodings_need_raw_bytes() {
+        let path = std::env::temp_dir().join("password_crack_utf8_only.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a'],
+            min_password_len: 1,
+            max_password_len: 1,
+            skip: 0,
+            limit: None,
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 1, strategy.clone());
+        cracker.register_verifier::<Utf8Only>("application/pdf");
+        cracker.set_encodings(vec![PasswordEncoding::Nfd, PasswordEncoding::Cp936]);
+        let res = cracker.start();
+        assert!(matches!(
+            res,
+            Err(Errors::UnsupportedEncoding {
+                encoding: PasswordEncoding::Cp936,
+                ..
+            })
+        ));
+        let mut cracker = Cracker::new(path.display().to_string(), 1, strategy);
+        cracker.register_verifier::<Utf8Only>("application/pdf");
+        cracker.set_encodings(vec![PasswordEncoding::Utf8, PasswordEncoding::Nfd]);
+        assert_eq!(cracker.start().unwrap(), Outcome::NotFound);
+        // the stream is left for the next search
+        let strategy = Strategy::Stream {
+            source: CandidateStream::new(Cursor::new("a\nb\n")),
+            encoding: DictEncoding::Utf8,
+            rules: vec![],
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Utf8Only>("application/pdf");
+        cracker.set_encodings(vec![PasswordEncoding::Cp936]);
+        assert!(matches!(
+            cracker.start(),
+            Err(Errors::UnsupportedEncoding { .. })
+        ));
+        cracker.set_encodings(vec![PasswordEncoding::Utf8]);
+        assert_eq!(cracker.start().unwrap(), Outcome::NotFound);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cancel_running_search() {
+        let path = std::env::temp_dir().join("password_crack_cancel.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j'],
+            min_password_len: 1,
+            max_password_len: 12,
+            skip: 0,
+            limit: None,
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Never>("application/pdf");
+        let cancel_token = cracker.cancel_token();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            cancel_token.cancel();
+        });
+        let res = cracker.start();
+        canceller.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(res, Ok(Outcome::Cancelled { .. })));
+    }
+
+    #[test]
+    fn restore_checkpoint() {
+        let path = std::env::temp_dir().join("password_crack_restore.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a', 'b', 'c'],
+            min_password_len: 1,
+            max_password_len: 3,
+            skip: 0,
+            limit: None,
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Recorder>("application/pdf");
+        // each worker stops after its first batch
+        cracker.cancel();
+        assert_eq!(cracker.start().unwrap(), Outcome::Cancelled { tested: 2 });
+        let session = cracker.checkpoint().unwrap();
+        assert_eq!(session.positions, vec![2, 3]);
+        assert_eq!(RECORDED.lock().unwrap().len(), 2);
+
+        let mut restored = Cracker::restore(session).unwrap();
+        restored.register_verifier::<Recorder>("application/pdf");
+        assert_eq!(restored.tested_count(), 2);
+        assert_eq!(restored.start().unwrap(), Outcome::NotFound);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut recorded = RECORDED.lock().unwrap().clone();
+        recorded.sort();
+        recorded.dedup();
+        assert_eq!(recorded.len(), 3 + 9 + 27);
+        assert_eq!(RECORDED.lock().unwrap().len(), 3 + 9 + 27);
+    }
+
+    #[test]
+    fn cancel_after_last_candidate() {
+        let path = std::env::temp_dir().join("password_crack_cancel_exhausted.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a', 'b', 'c'],
+            min_password_len: 1,
+            max_password_len: 2,
+            skip: 0,
+            limit: None,
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Never>("application/pdf");
+        // every worker runs out of candidates before checking the token
+        cracker.cancel();
+        assert_eq!(cracker.start().unwrap(), Outcome::NotFound);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cracker.tested_count(), 3 + 9);
+    }
+
+    #[test]
+    fn skip_and_limit() {
+        let path = std::env::temp_dir().join("password_crack_skip_limit.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a', 'b', 'c'],
+            min_password_len: 1,
+            max_password_len: 3,
+            skip: 10,
+            limit: Some(20),
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 3, strategy);
+        cracker.register_verifier::<Never>("application/pdf");
+        assert_eq!(cracker.count().unwrap(), Some(20));
+        assert_eq!(cracker.start().unwrap(), Outcome::NotFound);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cracker.tested_count(), 20);
+    }
+
+    #[test]
+    fn events_of_exhausted_search() {
+        let path = std::env::temp_dir().join("password_crack_events.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::GenPasswords {
+            charsets: vec!['a', 'b', 'c'],
+            min_password_len: 1,
+            max_password_len: 3,
+            skip: 0,
+            limit: None,
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Never>("application/pdf");
+        let events = cracker.events();
+        assert_eq!(cracker.start().unwrap(), Outcome::NotFound);
+        std::fs::remove_file(&path).unwrap();
+
+        let events: Vec<Event> = events.try_iter().collect();
+        assert_eq!(
+            events[0],
+            Event::TargetInfo {
+                mime_type: "application/pdf".to_string(),
+                description: None
+            }
+        );
+        let started = events
+            .iter()
+            .filter(|e| matches!(e, Event::WorkerStarted { .. }))
+            .count();
+        assert_eq!(started, 2);
+        let tested: u64 = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::WorkerFinished { tested, .. } => Some(*tested),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(tested, 3 + 9 + 27);
+        assert!(matches!(
+            events.last(),
+            Some(Event::Progress { tested: 39, .. })
+        ));
+        assert_eq!(
+            Some(cracker.tested_count() as usize),
+            cracker.count().unwrap()
+        );
+    }
+
+    #[test]
+    fn typos_counted_then_tested() {
+        let path = std::env::temp_dir().join("password_crack_typos.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let strategy = Strategy::Typos {
+            password: "secrte".to_string(),
+            max_edits: 1,
+            charset: vec![],
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Secret>("application/pdf");
+        assert!(cracker.count().unwrap().unwrap() > 1);
+        assert_eq!(
+            cracker.start().unwrap(),
+            Outcome::Found {
+                password: "secret".to_string(),
+                encoding: PasswordEncoding::Utf8
+            }
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let strategy = Strategy::Typos {
+            password: "secret".to_string(),
+            max_edits: 10,
+            charset: vec!['1'],
+        };
+        let cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        assert!(matches!(cracker.count(), Err(Errors::InvalidTypos { .. })));
+    }
+
+    #[test]
+    fn candidate_list_generated_once() {
+        let cached = CachedStrategy::new(Strategy::Typos {
+            password: "secret".to_string(),
+            max_edits: 1,
+            charset: vec!['1'],
+        });
+        let clone = cached.clone();
+        match (cached.candidate_list(), clone.candidate_list()) {
+            (Ok(Some(CandidateList::Typos(a))), Ok(Some(CandidateList::Typos(b)))) => {
+                assert!(Arc::ptr_eq(&a, &b));
+                assert_eq!(get_password_count(&clone).unwrap(), Some(a.len()));
+            }
+            _ => panic!("typos are kept in memory"),
+        }
+        let cached = CachedStrategy::new(Strategy::Mask {
+            mask: "?d".to_string(),
+            custom_charsets: vec![],
+            increment: false,
+        });
+        assert!(cached.candidate_list().unwrap().is_none());
+    }
+
+    #[test]
+    fn stream_candidates() {
+        let path = std::env::temp_dir().join("password_crack_stream.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let candidates: String = (0..5000).map(|i| format!("{}\n", i)).collect();
+        let strategy = Strategy::Stream {
+            source: CandidateStream::new(Cursor::new(candidates + "secret\nlast\n")),
+            encoding: DictEncoding::Utf8,
+            rules: vec![],
+        };
+        let mut cracker = Cracker::new(path.display().to_string(), 3, strategy);
+        cracker.register_verifier::<Secret>("application/pdf");
+        assert_eq!(cracker.count().unwrap(), None);
+        assert_eq!(
+            cracker.start().unwrap(),
+            Outcome::Found {
+                password: "secret".to_string(),
+                encoding: PasswordEncoding::Utf8
+            }
+        );
+        // the stream cannot be read twice
+        assert!(matches!(cracker.start(), Err(Errors::StreamConsumed)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dictionary_with_rules() {
+        let path = std::env::temp_dir().join("password_crack_rules.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        let dictionary = std::env::temp_dir().join("password_crack_rules.txt");
+        std::fs::write(&dictionary, "one\nsecre\nSECRET\n").unwrap();
+        let rules = std::env::temp_dir().join("password_crack_rules.rule");
+        std::fs::write(&rules, ":\nu\n$t\n<3 r\n").unwrap();
+        let strategy = Strategy::PasswordFile {
+            path: dictionary.clone(),
+            encoding: DictEncoding::Utf8,
+            rules: vec![rules.clone()],
+        };
+
+        let mut cracker = Cracker::new(path.display().to_string(), 3, strategy.clone());
+        cracker.register_verifier::<Never>("application/pdf");
+        assert_eq!(cracker.count().unwrap(), Some(3 * 4));
+        assert_eq!(cracker.start().unwrap(), Outcome::NotFound);
+        // rejected candidates count as tested
+        assert_eq!(cracker.tested_count(), 3 * 4);
+
+        let mut cracker = Cracker::new(path.display().to_string(), 2, strategy);
+        cracker.register_verifier::<Secret>("application/pdf");
+        let found = cracker.start();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&dictionary).unwrap();
+        std::fs::remove_file(&rules).unwrap();
+        assert_eq!(
+            found.unwrap(),
+            Outcome::Found {
+                password: "secret".to_string(),
+                encoding: PasswordEncoding::Utf8
+            }
+        );
+    }
+}
@@ -1,8 +1,4 @@
-use std::{collections::HashMap, iter::repeat};
-
-use ahash::AHashMap;
-
-use crate::Errors;
+use crate::{candidates::Candidates, Errors};
 pub fn password_generator_count(
     charset: &Vec<char>,
     min_size: usize,
@@ -23,89 +19,38 @@ pub fn password_generator_count(
     }
     Ok(total_password_count)
 }
+/// Every password made of `charset` with a length in `min_size..=max_size`, shortest first.
+///
+/// The candidates form a mixed-radix sequence, so skipping ahead with [`Iterator::nth`] or
+/// [`Candidates::advance_by`] is computed directly instead of generating the skipped candidates.
+/// Workers sharding the keyspace only generate their own candidates.
 pub struct PasswordGenerator {
     charset: Vec<char>,
-    charset_indices: AHashMap<char, usize>,
-    charset_len: usize,
-    charset_first: char,
-    charset_last: char,
-    max_size: usize,
-    current_len: usize,
-    current_index: usize,
+    /// UTF-8 encoding of every char of the charset
+    charset_bytes: Vec<Vec<u8>>,
+    min_size: usize,
+    /// charset index of every character of the next candidate, the last one changes fastest
+    digits: Vec<usize>,
     generated_count: usize,
     total_to_generate: usize,
-    password: Vec<char>,
 }
 impl Iterator for PasswordGenerator {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.password.len() > self.max_size {
-            return None;
-        }
-
-        // first password
-        if self.generated_count == 0 {
-            self.generated_count += 1;
-            return Some(self.password.iter().collect());
-        }
-
-        // end of search space
-        if self.generated_count == self.total_to_generate {
+        if self.generated_count >= self.total_to_generate {
             return None;
         }
+        let password = self.digits.iter().map(|&d| self.charset[d]).collect();
+        self.advance(1);
+        Some(password)
+    }
 
-        // check if we need to increase the length of the password
-        if self.current_len == self.current_index + 1
-            && !self.password.iter().any(|&c| c != self.charset_last)
-        {
-            // increase length and reset letters
-            self.current_index += 1;
-            self.current_len += 1;
-            self.password = vec![self.charset_first; self.current_len];
-            // let possibilities = self.charset_len.pow(self.current_len as u32);
-        } else {
-            let current_char = *self.password.get(self.current_index).unwrap();
-            if current_char == self.charset_last {
-                // current char reached the end of the charset, reset current and bump previous
-                let at_prev = self
-                    .password
-                    .iter()
-                    .rposition(|&c| c != self.charset_last)
-                    .unwrap_or_else(|| {
-                        panic!(
-                            "Must find something else than {} in {:?}",
-                            self.charset_last, self.password
-                        )
-                    });
-                let next_prev = if at_prev == self.charset_len - 1 {
-                    self.charset.get(self.charset_len - 1).unwrap()
-                } else {
-                    let prev_char = *self.password.get(at_prev).unwrap();
-                    let prev_index_charset =
-                        self.charset.iter().position(|&c| c == prev_char).unwrap();
-                    self.charset.get(prev_index_charset + 1).unwrap()
-                };
-
-                self.password[self.current_index] = self.charset_first;
-                self.password[at_prev] = *next_prev;
-
-                // reset all chars after previous
-                for (i, x) in self.password.iter_mut().enumerate() {
-                    if *x == self.charset_last && i > at_prev {
-                        *x = self.charset_first
-                    }
-                }
-            } else {
-                // hot-path: increment current char (not at the end of charset)
-                let at = *self.charset_indices.get(&current_char).unwrap();
-                let next = *self.charset.get(at + 1).unwrap();
-                self.password[self.current_index] = next;
-            }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n > 0 {
+            self.advance(n);
         }
-        self.generated_count += 1;
-        // TODO explore using a lending iterator to avoid allocation
-        Some(self.password.iter().collect::<String>())
+        self.next()
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -113,39 +58,151 @@ impl Iterator for PasswordGenerator {
         (remaining, Some(remaining))
     }
 }
+impl Candidates for PasswordGenerator {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.generated_count >= self.total_to_generate {
+            return Ok(false);
+        }
+        buffer.clear();
+        for &d in &self.digits {
+            buffer.extend_from_slice(&self.charset_bytes[d]);
+        }
+        self.advance(1);
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        self.advance(n);
+        Ok(self.generated_count < self.total_to_generate)
+    }
+}
 impl PasswordGenerator {
     pub fn new(charset: Vec<char>, min_size: usize, max_size: usize) -> Self {
-        let charset_len = charset.len();
-        let charset_first = *charset.first().expect("charset non empty");
-        let charset_last = *charset.last().expect("charset non empty");
-        // pre-compute charset indices
-        let charset_indices = charset
-            .iter()
-            .enumerate()
-            .map(|(i, c)| (*c, i))
-            .collect::<AHashMap<char, usize>>();
-
-        let password = vec![charset_first; min_size];
-        let current_len = password.len();
-        let current_index = current_len - 1;
-
-        let generated_count = 0;
+        assert!(!charset.is_empty(), "charset non empty");
         let total_to_generate = password_generator_count(&charset, min_size, max_size).unwrap();
+        let charset_bytes = charset
+            .iter()
+            .map(|c| c.to_string().into_bytes())
+            .collect();
 
         PasswordGenerator {
             charset,
-            charset_indices,
-            charset_len,
-            charset_first,
-            charset_last,
-            max_size,
-            current_len,
-            current_index,
-            generated_count,
+            charset_bytes,
+            min_size,
+            digits: vec![0; min_size],
+            generated_count: 0,
             total_to_generate,
-            password,
         }
     }
+
+    /// Candidate at `index` in the keyspace, without moving the generator.
+    pub fn at(&self, index: usize) -> Option<String> {
+        if index >= self.total_to_generate {
+            return None;
+        }
+        Some(
+            self.digits_at(index)
+                .iter()
+                .map(|&d| self.charset[d])
+                .collect(),
+        )
+    }
+
+    /// Index of `password` in the keyspace, `None` if it cannot be generated.
+    pub fn index_of(&self, password: &str) -> Option<usize> {
+        let base = self.charset.len();
+        let digits = password
+            .chars()
+            .map(|c| self.charset.iter().position(|&x| x == c))
+            .collect::<Option<Vec<usize>>>()?;
+        if digits.len() < self.min_size {
+            return None;
+        }
+        // every shorter password comes first
+        let mut index = (self.min_size..digits.len())
+            .try_fold(0usize, |acc, len| acc.checked_add(base.checked_pow(len as u32)?))?;
+        let mut offset = 0usize;
+        for digit in digits {
+            offset = offset.checked_mul(base)?.checked_add(digit)?;
+        }
+        index = index.checked_add(offset)?;
+        (index < self.total_to_generate).then_some(index)
+    }
+
+    /// Positions the generator so that the next candidate is the one at `index`.
+    pub fn seek(&mut self, index: usize) {
+        if index >= self.total_to_generate {
+            self.generated_count = self.total_to_generate;
+        } else {
+            self.digits = self.digits_at(index);
+            self.generated_count = index;
+        }
+    }
+
+    /// Stops the generator after `count` more candidates.
+    pub fn limit(&mut self, count: usize) {
+        self.total_to_generate = self
+            .total_to_generate
+            .min(self.generated_count.saturating_add(count));
+    }
+
+    /// Positions the generator so that the next candidate is `password`.
+    /// Returns `false`, without moving, if `password` is not in the keyspace.
+    pub fn seek_to(&mut self, password: &str) -> bool {
+        match self.index_of(password) {
+            Some(index) => {
+                self.seek(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves `n` candidates forward.
+    fn advance(&mut self, n: usize) {
+        let target = self.generated_count.saturating_add(n);
+        if target >= self.total_to_generate {
+            self.generated_count = self.total_to_generate;
+            return;
+        }
+        // add `n` to the digits, from the last one
+        let base = self.charset.len();
+        let mut carry = n;
+        for digit in self.digits.iter_mut().rev() {
+            if carry == 0 {
+                break;
+            }
+            let sum = *digit + carry % base;
+            *digit = sum % base;
+            carry = carry / base + sum / base;
+        }
+        if carry == 0 {
+            self.generated_count = target;
+        } else {
+            // overflowed into longer passwords
+            self.seek(target);
+        }
+    }
+
+    /// Charset indices of the candidate at `index`, which must be in the keyspace.
+    fn digits_at(&self, index: usize) -> Vec<usize> {
+        let base = self.charset.len();
+        let mut len = self.min_size;
+        let mut offset = index;
+        // `total_to_generate` fits in a usize, so does every length count below it
+        let mut len_count = base.pow(len as u32);
+        while offset >= len_count {
+            offset -= len_count;
+            len += 1;
+            len_count *= base;
+        }
+        let mut digits = vec![0; len];
+        for digit in digits.iter_mut().rev() {
+            *digit = offset % base;
+            offset /= base;
+        }
+        digits
+    }
 }
 
 #[cfg(test)]
@@ -153,10 +210,88 @@ impl PasswordGenerator {
 mod test {
 
     use super::PasswordGenerator;
+    use crate::candidates::Candidates;
     use std::clone;
     use std::fmt::Write;
     use std::time::Instant;
 
+    #[test]
+    fn generate_in_order() {
+        let passwords: Vec<String> = PasswordGenerator::new(vec!['a', 'b', 'c'], 1, 3).collect();
+        assert_eq!(passwords.len(), 3 + 9 + 27);
+        assert_eq!(&passwords[..5], &["a", "b", "c", "aa", "ab"]);
+        assert_eq!(passwords[11], "cc");
+        assert_eq!(passwords[12], "aaa");
+        assert_eq!(passwords.last().unwrap(), "ccc");
+    }
+
+    #[test]
+    fn shards_cover_keyspace_once() {
+        let chars = vec!['0', '1', '2', '3', '4'];
+        let all: Vec<String> = PasswordGenerator::new(chars.clone(), 2, 4).collect();
+        let worker_count = 3;
+        let mut sharded = vec![];
+        for start in 0..worker_count {
+            let shard = PasswordGenerator::new(chars.clone(), 2, 4)
+                .skip(start)
+                .step_by(worker_count);
+            sharded.extend(shard.enumerate().map(|(i, p)| (start + i * worker_count, p)));
+        }
+        sharded.sort();
+        let sharded: Vec<String> = sharded.into_iter().map(|(_, p)| p).collect();
+        assert_eq!(sharded, all);
+    }
+
+    #[test]
+    fn nth_past_the_end() {
+        let mut generator = PasswordGenerator::new(vec!['a', 'b'], 1, 2);
+        assert_eq!(generator.nth(5), Some("bb".to_string()));
+        assert_eq!(generator.next(), None);
+        let mut generator = PasswordGenerator::new(vec!['a', 'b'], 1, 2);
+        assert_eq!(generator.nth(6), None);
+        assert_eq!(generator.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn fill_buffer() {
+        let mut generator = PasswordGenerator::new(vec!['a', '中'], 1, 2);
+        let mut buffer = Vec::new();
+        assert!(generator.next_into(&mut buffer).unwrap());
+        assert_eq!(buffer, b"a");
+        assert!(generator.advance_by(2).unwrap());
+        assert!(generator.next_into(&mut buffer).unwrap());
+        assert_eq!(buffer, "a中".as_bytes());
+        generator.limit(1);
+        assert!(generator.next_into(&mut buffer).unwrap());
+        assert_eq!(buffer, "中a".as_bytes());
+        assert!(!generator.next_into(&mut buffer).unwrap());
+    }
+
+    #[test]
+    fn random_access() {
+        let generator = PasswordGenerator::new(vec!['a', 'b', 'c'], 2, 4);
+        for (index, password) in PasswordGenerator::new(vec!['a', 'b', 'c'], 2, 4).enumerate() {
+            assert_eq!(generator.at(index).as_ref(), Some(&password));
+            assert_eq!(generator.index_of(&password), Some(index));
+        }
+        assert_eq!(generator.at(9 + 27 + 81), None);
+        assert_eq!(generator.index_of("a"), None);
+        assert_eq!(generator.index_of("aaaaa"), None);
+        assert_eq!(generator.index_of("abd"), None);
+    }
+
+    #[test]
+    fn seek_to_candidate() {
+        let mut generator = PasswordGenerator::new(vec!['a', 'b', 'c'], 1, 3);
+        assert!(generator.seek_to("cc"));
+        assert_eq!(generator.next(), Some("cc".to_string()));
+        assert!(!generator.seek_to("dd"));
+        assert_eq!(generator.next(), Some("aaa".to_string()));
+        generator.seek(2);
+        assert_eq!(generator.next(), Some("c".to_string()));
+        assert_eq!(generator.size_hint(), (36, Some(36)));
+    }
+
     #[test]
     fn tsd() {
         let chars = vec!['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
@@ -1,10 +1,123 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader, Read},
+    collections::{hash_map::RandomState, HashSet},
+    fs::{self, File},
+    hash::{BuildHasher, Hasher},
+    io::{self, BufRead, BufReader, Read},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
 };
 
-use crate::errors::Errors;
+use crossbeam_channel::{Receiver, Sender};
+
+use parse_display::{Display, FromStr};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    candidates::Candidates,
+    errors::Errors,
+    events::{Event, EventEmitter},
+};
+
+/// Encoding of the lines of a dictionary.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Display, FromStr, Serialize, Deserialize)]
+pub enum DictEncoding {
+    /// lines are tested byte for byte, whatever their encoding
+    #[default]
+    #[display("utf-8")]
+    Utf8,
+    /// transcoded to UTF-8
+    #[display("gbk")]
+    Gbk,
+    /// ISO-8859-1, transcoded to UTF-8
+    #[display("latin1")]
+    Latin1,
+    /// transcoded to UTF-8, lines end with a UTF-16 newline
+    #[display("utf-16le")]
+    Utf16Le,
+}
+impl DictEncoding {
+    pub fn to_string() -> String {
+        format!(
+            "{},{},{},{}",
+            DictEncoding::Utf8,
+            DictEncoding::Gbk,
+            DictEncoding::Latin1,
+            DictEncoding::Utf16Le
+        )
+    }
+}
+
+/// Compression of a dictionary, detected from its first bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+impl Compression {
+    pub fn detect(dictionary_path: &Path) -> Result<Self, Errors> {
+        let mut magic = Vec::with_capacity(6);
+        File::open(dictionary_path)?
+            .take(6)
+            .read_to_end(&mut magic)?;
+        let compression = if magic.starts_with(&[0x1F, 0x8B]) {
+            Compression::Gzip
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if magic.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        };
+        Ok(compression)
+    }
+}
+
+/// Counts the bytes read from the dictionary file, before decompression.
+struct CountingReader {
+    file: File,
+    stats: Arc<DictionaryStats>,
+}
+impl Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.stats
+            .read_bytes
+            .fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+/// Progress of the dictionary reader, and lines which could not be tested verbatim
+/// counted over all workers.
+#[derive(Debug, Default)]
+pub struct DictionaryStats {
+    read_bytes: AtomicU64,
+    transcoded: AtomicU64,
+    invalid: AtomicU64,
+}
+impl DictionaryStats {
+    /// Bytes read from the dictionary file, compressed if the file is.
+    pub fn read_bytes(&self) -> u64 {
+        self.read_bytes.load(Ordering::Relaxed)
+    }
+    /// Lines converted from the dictionary encoding to UTF-8.
+    pub fn transcoded(&self) -> u64 {
+        self.transcoded.load(Ordering::Relaxed)
+    }
+    /// Lines not valid in the dictionary encoding, tested as raw bytes instead.
+    pub fn invalid(&self) -> u64 {
+        self.invalid.load(Ordering::Relaxed)
+    }
+}
 
 // pub fn password_reader_count(dictionary_path: &PathBuf) -> Result<usize, Errors> {
 //     let file = File::open(dictionary_path).expect("Unable to open file");
@@ -23,12 +136,18 @@ use crate::errors::Errors;
 //     }
 //     Ok(total_count)
 // }
-pub fn password_reader_count(dictionary_path: &PathBuf) -> Result<usize, Errors> {
-    let file = File::open(dictionary_path).expect("Unable to open file");
+pub fn password_reader_count(
+    dictionary_path: &PathBuf,
+    encoding: DictEncoding,
+) -> Result<usize, Errors> {
+    let file = File::open(dictionary_path)?;
     let mut reader = BufReader::new(file);
     let mut buffer = [0u8; 4096];
     let mut line_count = 0;
-    let mut byte_count = 0;
+    let mut last_unit = None;
+    // a UTF-16 unit may be split between two reads
+    let mut offset = 0usize;
+    let mut low_byte = 0u8;
 
     loop {
         let bytes_read = reader.read(&mut buffer)?;
@@ -36,54 +155,706 @@ pub fn password_reader_count(dictionary_path: &PathBuf) -> Result<usize, Errors>
             break;
         }
 
-        byte_count += bytes_read;
-        for i in 0..bytes_read {
-            if buffer[i] == b'\n' {
-                line_count += 1;
+        for &byte in &buffer[..bytes_read] {
+            if encoding != DictEncoding::Utf16Le {
+                if byte == b'\n' {
+                    line_count += 1;
+                }
+                last_unit = Some(byte as u16);
+            } else if offset % 2 == 0 {
+                low_byte = byte;
+            } else {
+                let unit = u16::from_le_bytes([low_byte, byte]);
+                if unit == u16::from(b'\n') {
+                    line_count += 1;
+                }
+                last_unit = Some(unit);
             }
+            offset += 1;
         }
     }
 
     // Check if last line doesn't end with a newline character
-    if buffer[byte_count % 4096 - 1] != b'\n' {
+    if encoding == DictEncoding::Utf16Le && offset % 2 == 1 {
+        line_count += 1;
+    } else if last_unit.map_or(false, |u| u != u16::from(b'\n')) {
         line_count += 1;
     }
     Ok(line_count)
 }
 pub struct PasswordReader {
-    reader: BufReader<File>,
-    line_buffer: String,
+    reader: Box<dyn BufRead + Send>,
+    encoding: DictEncoding,
+    first_line: bool,
 }
 impl PasswordReader {
-    pub fn new(dictionary_path: &Path) -> Self {
-        let file = File::open(dictionary_path).expect("Unable to open file");
-        let reader = BufReader::new(file);
+    /// Opens a plain or compressed dictionary, counting the bytes read from it in `stats`.
+    pub fn new(
+        dictionary_path: &Path,
+        encoding: DictEncoding,
+        stats: Arc<DictionaryStats>,
+    ) -> Result<Self, Errors> {
+        let compression = Compression::detect(dictionary_path)?;
+        let file = CountingReader {
+            file: File::open(dictionary_path)?,
+            stats,
+        };
+        let reader: Box<dyn Read + Send> = match compression {
+            Compression::None => Box::new(file),
+            // multi decoders to read concatenated streams like `cat a.gz b.gz` to the end
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(file)),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+        };
+        Ok(PasswordReader::from_reader(
+            BufReader::new(reader),
+            encoding,
+        ))
+    }
+    pub fn from_reader(reader: impl BufRead + Send + 'static, encoding: DictEncoding) -> Self {
         PasswordReader {
-            reader,
-            line_buffer: String::new(),
+            reader: Box::new(reader),
+            encoding,
+            first_line: true,
+        }
+    }
+
+    /// Reads the next line without its line ending, returns `false` at the end of the file.
+    pub fn read_line(&mut self, line: &mut Vec<u8>) -> io::Result<bool> {
+        line.clear();
+        if self.encoding == DictEncoding::Utf16Le {
+            if read_utf16le_line(&mut self.reader, line)? == 0 {
+                return Ok(false);
+            }
+            if self.first_line && line.starts_with(&[0xFF, 0xFE]) {
+                line.drain(..2);
+            }
+            if line.ends_with(&[b'\n', 0]) {
+                line.truncate(line.len() - 2);
+                if line.ends_with(&[b'\r', 0]) {
+                    line.truncate(line.len() - 2);
+                }
+            }
+        } else {
+            // read_until to reuse the buffer and keep the bytes as they are
+            if self.reader.read_until(b'\n', line)? == 0 {
+                return Ok(false);
+            }
+            if line.ends_with(b"\n") {
+                line.pop();
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+            }
+        }
+        self.first_line = false;
+        Ok(true)
+    }
+}
+
+/// Lines sent at once by the dictionary reader to a worker.
+const BATCH_LINES: usize = 1024;
+/// Batches waiting in the channel of each worker, enough for the reader to keep the others
+/// busy while one worker is slower for a while.
+const CHANNEL_BATCHES: usize = 16;
+
+/// Lines for one worker, stored back to back.
+#[derive(Default)]
+pub struct LineBatch {
+    bytes: Vec<u8>,
+    ends: Vec<usize>,
+}
+impl LineBatch {
+    pub(crate) fn push(&mut self, line: &[u8]) {
+        self.bytes.extend_from_slice(line);
+        self.ends.push(self.bytes.len());
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.ends.len()
+    }
+    pub(crate) fn line(&self, i: usize) -> &[u8] {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] };
+        &self.bytes[start..self.ends[i]]
+    }
+}
+
+pub type LineReceiver = Receiver<Result<LineBatch, Errors>>;
+
+/// Candidates streamed from a pipe or any other reader, one per line.
+///
+/// A stream can only be read once, clones share it. A stream deserialized from a
+/// [`crate::Session`] reads the standard input, so that a search restored with the same
+/// candidates piped in again skips those already tested.
+#[derive(Clone)]
+pub struct CandidateStream {
+    reader: Arc<Mutex<Option<Box<dyn Read + Send>>>>,
+}
+impl CandidateStream {
+    pub fn new(reader: impl Read + Send + 'static) -> Self {
+        CandidateStream {
+            reader: Arc::new(Mutex::new(Some(Box::new(reader)))),
+        }
+    }
+    pub fn stdin() -> Self {
+        CandidateStream::new(io::stdin())
+    }
+    pub(crate) fn take(&self) -> Result<Box<dyn Read + Send>, Errors> {
+        self.reader
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(Errors::StreamConsumed)
+    }
+}
+impl PartialEq for CandidateStream {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.reader, &other.reader)
+    }
+}
+impl Eq for CandidateStream {}
+impl std::fmt::Debug for CandidateStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CandidateStream")
+    }
+}
+
+/// Dictionary files read one after the other as a single list of candidates.
+#[derive(Clone, Debug)]
+pub struct DictionaryList {
+    pub files: Vec<PathBuf>,
+    pub encoding: DictEncoding,
+    /// skip the lines already read, within and across files
+    pub dedup: bool,
+}
+impl DictionaryList {
+    /// Lists the files of `paths`, directories are read recursively in file name order.
+    pub fn new(paths: &[PathBuf], encoding: DictEncoding, dedup: bool) -> Result<Self, Errors> {
+        let mut files = vec![];
+        for path in paths {
+            push_files(path, &mut files)?;
+        }
+        Ok(DictionaryList {
+            files,
+            encoding,
+            dedup,
+        })
+    }
+    /// `false` if the number of lines is only known after reading them all,
+    /// with compressed files or de-duplication.
+    pub fn counted(&self) -> Result<bool, Errors> {
+        if self.dedup {
+            return Ok(false);
+        }
+        for file in &self.files {
+            if Compression::detect(file)? != Compression::None {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    pub fn count(&self) -> Result<Option<usize>, Errors> {
+        // counting the lines of a compressed dictionary would decompress it twice
+        if !self.counted()? {
+            return Ok(None);
         }
+        let mut count = 0;
+        for file in &self.files {
+            count += password_reader_count(file, self.encoding)?;
+        }
+        Ok(Some(count))
+    }
+    /// Size of all files, to measure the progress in bytes when the lines are not counted.
+    pub fn size(&self) -> Result<u64, Errors> {
+        let mut size = 0;
+        for file in &self.files {
+            size += fs::metadata(file)?.len();
+        }
+        Ok(size)
+    }
+    /// Opens the files one by one, announcing each of them with [`Event::DictionaryStarted`].
+    pub fn readers(
+        self,
+        stats: Arc<DictionaryStats>,
+        emitter: EventEmitter,
+    ) -> impl Iterator<Item = Result<PasswordReader, Errors>> + Send {
+        let total = self.files.len();
+        let encoding = self.encoding;
+        self.files.into_iter().enumerate().map(move |(i, path)| {
+            emitter.emit(Event::DictionaryStarted {
+                path: path.clone(),
+                number: i + 1,
+                total,
+            });
+            PasswordReader::new(&path, encoding, stats.clone())
+        })
+    }
+}
+
+fn push_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), Errors> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            push_files(&entry, files)?;
+        }
+    } else {
+        // fail before the search starts if the file is missing
+        fs::metadata(path)?;
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Lines already read, as 128 bits hashes so that distinct lines practically never collide.
+#[derive(Default)]
+pub(crate) struct SeenLines {
+    hashes: HashSet<u128>,
+    keys: (RandomState, RandomState),
+}
+impl SeenLines {
+    /// Returns `false` if `line` was already read.
+    pub(crate) fn insert(&mut self, line: &[u8]) -> bool {
+        let mut high = self.keys.0.build_hasher();
+        let mut low = self.keys.1.build_hasher();
+        high.write(line);
+        low.write(line);
+        self.hashes
+            .insert((u128::from(high.finish()) << 64) | u128::from(low.finish()))
     }
 }
-impl Iterator for PasswordReader {
-    type Item = String;
 
-    fn next(&mut self) -> Option<Self::Item> {
+/// Reads the dictionaries once on its own thread and hands their lines out to the workers.
+///
+/// Like with the other strategies, worker `w` of `n` gets the lines `w, w + n, w + 2n...`,
+/// starting from the line at its position in `starts`. With `broadcast`, every worker gets
+/// every line from its start, to apply its share of the rules to it.
+///
+/// Every worker has its own channel rather than sharing one: the lines of a shared channel
+/// would go to whichever worker is free, and the positions saved in a session would no longer
+/// tell which lines were tested. The reader blocks once the channel of a worker is full, so
+/// the channels buffer several batches to absorb the speed differences.
+pub fn feed_workers(
+    readers: impl Iterator<Item = Result<PasswordReader, Errors>> + Send + 'static,
+    dedup: bool,
+    starts: Vec<u64>,
+    broadcast: bool,
+) -> Result<(Vec<LineReceiver>, JoinHandle<()>), Errors> {
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..starts.len())
+        .map(|_| crossbeam_channel::bounded(CHANNEL_BATCHES))
+        .unzip();
+    let handle = thread::Builder::new()
+        .name("dictionary-reader".to_string())
+        .spawn(move || read_lines(readers, dedup, starts, broadcast, senders))?;
+    Ok((receivers, handle))
+}
+
+fn read_lines(
+    readers: impl Iterator<Item = Result<PasswordReader, Errors>>,
+    dedup: bool,
+    starts: Vec<u64>,
+    broadcast: bool,
+    senders: Vec<Sender<Result<LineBatch, Errors>>>,
+) {
+    let worker_count = starts.len();
+    let mut batches: Vec<LineBatch> = (0..worker_count).map(|_| LineBatch::default()).collect();
+    let mut seen = dedup.then(SeenLines::default);
+    let mut line = Vec::new();
+    let mut index = 0u64;
+    for reader in readers {
+        let mut reader = match reader {
+            Ok(reader) => reader,
+            Err(e) => {
+                let _ = senders[(index % worker_count as u64) as usize].send(Err(e));
+                return;
+            }
+        };
         loop {
-            self.line_buffer.clear();
-            let res = self.reader.read_line(&mut self.line_buffer);
-            match res {
-                Ok(0) => return None,
-                Ok(_) => {
-                    if self.line_buffer.ends_with('\n') {
-                        self.line_buffer.pop();
-                        if self.line_buffer.ends_with('\r') {
-                            self.line_buffer.pop();
-                        }
+            let worker = (index % worker_count as u64) as usize;
+            match reader.read_line(&mut line) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => {
+                    // the worker reports it, the others are stopped by the receiving side
+                    let _ = senders[worker].send(Err(e.into()));
+                    return;
+                }
+            }
+            if seen.as_mut().is_some_and(|seen| !seen.insert(&line)) {
+                continue;
+            }
+            let targets = if broadcast {
+                0..worker_count
+            } else {
+                worker..worker + 1
+            };
+            for worker in targets {
+                if index < starts[worker] {
+                    continue;
+                }
+                batches[worker].push(&line);
+                if batches[worker].len() == BATCH_LINES {
+                    let batch = std::mem::take(&mut batches[worker]);
+                    if senders[worker].send(Ok(batch)).is_err() {
+                        // the search is over
+                        return;
                     }
-                    return Some(self.line_buffer.clone());
                 }
-                Err(_) => continue,
             }
+            index += 1;
+        }
+    }
+    for (sender, batch) in senders.iter().zip(batches) {
+        if batch.len() > 0 {
+            let _ = sender.send(Ok(batch));
+        }
+    }
+}
+
+/// Dictionary lines of one worker, transcoded from the dictionary encoding.
+pub struct DictionaryCandidates {
+    batches: LineReceiver,
+    batch: LineBatch,
+    next_line: usize,
+    encoding: DictEncoding,
+    stats: Arc<DictionaryStats>,
+}
+impl DictionaryCandidates {
+    pub fn new(batches: LineReceiver, encoding: DictEncoding, stats: Arc<DictionaryStats>) -> Self {
+        DictionaryCandidates {
+            batches,
+            batch: LineBatch::default(),
+            next_line: 0,
+            encoding,
+            stats,
+        }
+    }
+}
+impl Candidates for DictionaryCandidates {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next_line == self.batch.len() {
+            match self.batches.recv() {
+                Ok(batch) => self.batch = batch?,
+                // the reader is done
+                Err(_) => return Ok(false),
+            }
+            self.next_line = 0;
+        }
+        let line = self.batch.line(self.next_line);
+        self.next_line += 1;
+        transcode_into(self.encoding, line, buffer, &self.stats);
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        let mut n = n;
+        loop {
+            let left = self.batch.len() - self.next_line;
+            if n <= left {
+                self.next_line += n;
+                return Ok(true);
+            }
+            // skip whole batches without transcoding their lines
+            n -= left;
+            match self.batches.recv() {
+                Ok(batch) => self.batch = batch?,
+                Err(_) => return Ok(false),
+            }
+            self.next_line = 0;
+        }
+    }
+}
+
+/// Every line of the dictionary at `path` transcoded to UTF-8, for dictionaries small enough
+/// to be kept in memory.
+pub fn load_lines(path: &Path, encoding: DictEncoding) -> Result<Vec<Vec<u8>>, Errors> {
+    let stats = Arc::new(DictionaryStats::default());
+    let mut reader = PasswordReader::new(path, encoding, stats.clone())?;
+    let mut lines = vec![];
+    let mut line = Vec::new();
+    while reader.read_line(&mut line)? {
+        let mut transcoded = Vec::with_capacity(line.len());
+        transcode_into(encoding, &line, &mut transcoded, &stats);
+        lines.push(transcoded);
+    }
+    Ok(lines)
+}
+
+/// Replaces the content of `buffer` with `line` transcoded to UTF-8, or with the raw bytes
+/// of `line` if it is not valid in `encoding`.
+fn transcode_into(
+    encoding: DictEncoding,
+    line: &[u8],
+    buffer: &mut Vec<u8>,
+    stats: &DictionaryStats,
+) {
+    buffer.clear();
+    let transcoded = match encoding {
+        DictEncoding::Utf8 => {
+            buffer.extend_from_slice(line);
+            if std::str::from_utf8(line).is_err() {
+                stats.invalid.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
+        DictEncoding::Latin1 => {
+            let mut utf8 = [0u8; 2];
+            for &byte in line {
+                buffer.extend_from_slice(char::from(byte).encode_utf8(&mut utf8).as_bytes());
+            }
+            true
+        }
+        DictEncoding::Gbk => decode_into(encoding_rs::GBK, line, buffer),
+        DictEncoding::Utf16Le => decode_into(encoding_rs::UTF_16LE, line, buffer),
+    };
+    if !transcoded {
+        stats.invalid.fetch_add(1, Ordering::Relaxed);
+        buffer.extend_from_slice(line);
+    } else if buffer != line {
+        stats.transcoded.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Decodes `line` into `buffer` as UTF-8, returns `false` if `line` is not valid in `encoding`.
+fn decode_into(
+    encoding: &'static encoding_rs::Encoding,
+    line: &[u8],
+    buffer: &mut Vec<u8>,
+) -> bool {
+    match encoding.decode_without_bom_handling_and_without_replacement(line) {
+        Some(decoded) => {
+            buffer.extend_from_slice(decoded.as_bytes());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Like `read_until(b'\n')` for UTF-16LE: only a whole `\n` unit ends the line.
+fn read_utf16le_line(reader: &mut impl BufRead, line: &mut Vec<u8>) -> io::Result<usize> {
+    loop {
+        if reader.read_until(b'\n', line)? == 0 || !line.ends_with(b"\n") {
+            break;
+        }
+        if line.len() % 2 == 1 {
+            // low byte of a unit, the line ends if its high byte is 0
+            let mut high = [0u8; 1];
+            if reader.read(&mut high)? == 0 {
+                break;
+            }
+            line.push(high[0]);
+            if high[0] == 0 {
+                break;
+            }
+        }
+    }
+    Ok(line.len())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{
+        feed_workers, password_reader_count, Compression, DictEncoding, DictionaryCandidates,
+        DictionaryList, DictionaryStats, PasswordReader,
+    };
+    use crate::{candidates::Candidates, events::EventEmitter};
+
+    /// Lines of every worker, and the line count.
+    fn read_all(
+        content: &[u8],
+        encoding: DictEncoding,
+        starts: Vec<u64>,
+    ) -> (Vec<Vec<Vec<u8>>>, usize, Arc<DictionaryStats>) {
+        let path = std::env::temp_dir().join(format!(
+            "password_crack_dict_{}_{}.txt",
+            encoding,
+            starts.len()
+        ));
+        std::fs::write(&path, content).unwrap();
+        let stats = Arc::new(DictionaryStats::default());
+        let reader = PasswordReader::new(&path, encoding, stats.clone()).unwrap();
+        let (receivers, handle) =
+            feed_workers(std::iter::once(Ok(reader)), false, starts, false).unwrap();
+        let mut buffer = Vec::new();
+        let mut lines = vec![];
+        for receiver in receivers {
+            let mut candidates = DictionaryCandidates::new(receiver, encoding, stats.clone());
+            let mut worker_lines = vec![];
+            while candidates.next_into(&mut buffer).unwrap() {
+                worker_lines.push(buffer.clone());
+            }
+            lines.push(worker_lines);
+        }
+        handle.join().unwrap();
+        let count = password_reader_count(&path, encoding).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (lines, count, stats)
+    }
+
+    #[test]
+    fn raw_bytes() {
+        let (lines, count, stats) = read_all(
+            b"abc\r\n\xd6\xd0\xce\xc4\n\nlast",
+            DictEncoding::Utf8,
+            vec![0],
+        );
+        assert_eq!(
+            lines[0],
+            vec![
+                b"abc".to_vec(),
+                vec![0xd6, 0xd0, 0xce, 0xc4],
+                vec![],
+                b"last".to_vec()
+            ]
+        );
+        assert_eq!(count, 4);
+        assert_eq!((stats.transcoded(), stats.invalid()), (0, 1));
+    }
+
+    #[test]
+    fn split_between_workers() {
+        let content: String = (0..5000).map(|i| format!("{}\n", i)).collect();
+        // the first worker already tested 0 and 3
+        let (lines, count, _) = read_all(content.as_bytes(), DictEncoding::Utf8, vec![6, 1, 2]);
+        assert_eq!(count, 5000);
+        assert_eq!(lines[0].len() + lines[1].len() + lines[2].len(), 4998);
+        assert_eq!(lines[0][0], b"6");
+        assert_eq!(lines[1][..2], [b"1".to_vec(), b"4".to_vec()]);
+        assert_eq!(lines[2].last().unwrap(), b"4997");
+    }
+
+    #[test]
+    fn skip_lines() {
+        let path = std::env::temp_dir().join("password_crack_dict_skip.txt");
+        let content: String = (0..3000).map(|i| format!("{}\n", i)).collect();
+        std::fs::write(&path, content).unwrap();
+        let stats = Arc::new(DictionaryStats::default());
+        let reader = PasswordReader::new(&path, DictEncoding::Utf8, stats.clone()).unwrap();
+        let (mut receivers, handle) =
+            feed_workers(std::iter::once(Ok(reader)), false, vec![0], false).unwrap();
+        let mut candidates =
+            DictionaryCandidates::new(receivers.remove(0), DictEncoding::Utf8, stats);
+        let mut buffer = Vec::new();
+        // across several batches
+        assert!(candidates.advance_by(2500).unwrap());
+        assert!(candidates.next_into(&mut buffer).unwrap());
+        assert_eq!(buffer, b"2500");
+        assert!(candidates.advance_by(499).unwrap());
+        assert!(!candidates.next_into(&mut buffer).unwrap());
+        assert!(!candidates.advance_by(1).unwrap());
+        handle.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn several_dictionaries() {
+        let dir = std::env::temp_dir().join("password_crack_dictionaries");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("b.txt"), "b\nshared\n").unwrap();
+        std::fs::write(dir.join("a.txt"), "a\nshared\n").unwrap();
+        std::fs::write(dir.join("sub").join("c.txt"), "c\na\n").unwrap();
+        let first = std::env::temp_dir().join("password_crack_first.txt");
+        std::fs::write(&first, "first\n").unwrap();
+
+        let paths = vec![first.clone(), dir.clone()];
+        let list = DictionaryList::new(&paths, DictEncoding::Utf8, false).unwrap();
+        assert_eq!(
+            list.files,
+            vec![
+                first.clone(),
+                dir.join("a.txt"),
+                dir.join("b.txt"),
+                dir.join("sub").join("c.txt")
+            ]
+        );
+        assert_eq!(list.count().unwrap(), Some(7));
+
+        let list = DictionaryList::new(&paths, DictEncoding::Utf8, true).unwrap();
+        assert_eq!(list.count().unwrap(), None);
+        let (receivers, handle) = feed_workers(
+            list.readers(
+                Arc::new(DictionaryStats::default()),
+                EventEmitter::default(),
+            ),
+            true,
+            vec![0],
+            false,
+        )
+        .unwrap();
+        handle.join().unwrap();
+        let batch = receivers[0].recv().unwrap().unwrap();
+        let lines: Vec<&[u8]> = (0..batch.len()).map(|i| batch.line(i)).collect();
+        assert_eq!(lines, vec![&b"first"[..], b"a", b"shared", b"b", b"c"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&first).unwrap();
+    }
+
+    #[test]
+    fn compressed() {
+        use std::io::Write;
+
+        let content: String = (0..1000).map(|i| format!("{}\n", i)).collect();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(content.as_bytes()).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let zstd = zstd::stream::encode_all(content.as_bytes(), 0).unwrap();
+
+        for (compressed, compression) in [(gzip, Compression::Gzip), (zstd, Compression::Zstd)] {
+            let path = std::env::temp_dir().join(format!("password_crack_{:?}.txt", compression));
+            std::fs::write(&path, &compressed).unwrap();
+            assert_eq!(Compression::detect(&path).unwrap(), compression);
+            let stats = Arc::new(DictionaryStats::default());
+            let reader = PasswordReader::new(&path, DictEncoding::Utf8, stats.clone()).unwrap();
+            let (receivers, handle) =
+                feed_workers(std::iter::once(Ok(reader)), false, vec![0, 1], false).unwrap();
+            handle.join().unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let lines: usize = receivers
+                .iter()
+                .flat_map(|r| r.try_iter())
+                .map(|b| b.unwrap().len())
+                .sum();
+            assert_eq!(lines, 1000);
+            assert_eq!(stats.read_bytes(), compressed.len() as u64);
         }
     }
+
+    #[test]
+    fn transcode_gbk() {
+        let (lines, count, stats) =
+            read_all(b"abc\n\xd6\xd0\xce\xc4\n\xff\n", DictEncoding::Gbk, vec![0]);
+        assert_eq!(
+            lines[0],
+            vec![b"abc".to_vec(), "中文".as_bytes().to_vec(), vec![0xff]]
+        );
+        assert_eq!(count, 3);
+        assert_eq!((stats.transcoded(), stats.invalid()), (1, 1));
+    }
+
+    #[test]
+    fn transcode_latin1() {
+        let (lines, _, stats) = read_all(b"caf\xe9\n", DictEncoding::Latin1, vec![0]);
+        assert_eq!(lines[0], vec!["café".as_bytes().to_vec()]);
+        assert_eq!((stats.transcoded(), stats.invalid()), (1, 0));
+    }
+
+    #[test]
+    fn transcode_utf16le() {
+        // BOM, "a\u{10a}" (0x0A as low byte), "中" without newline
+        let content = [
+            0xFF, 0xFE, b'a', 0, 0x0A, 0x01, b'\r', 0, b'\n', 0, 0x2D, 0x4E,
+        ];
+        let (lines, count, stats) = read_all(&content, DictEncoding::Utf16Le, vec![0]);
+        assert_eq!(
+            lines[0],
+            vec!["a\u{10a}".as_bytes().to_vec(), "中".as_bytes().to_vec()]
+        );
+        assert_eq!(count, 2);
+        assert_eq!((stats.transcoded(), stats.invalid()), (2, 0));
+    }
 }
@@ -1,121 +1,305 @@
 use crossbeam_channel::Sender;
 
-use infer::Type;
-
 use std::{
+    io::BufReader,
+    iter,
     path::Path,
     sync::{
-        atomic::{AtomicBool, AtomicU64},
+        atomic::{AtomicBool, Ordering},
         Arc,
     },
     thread::{self, JoinHandle},
 };
 
 use crate::{
-    errors::Errors, filter_for_worker_index, password_finder::Strategy,
-    password_gen::PasswordGenerator, password_reader::PasswordReader, Passwords,
+    candidates::{shard, Passwords},
+    control::{CancelToken, PauseToken},
+    encodings::{Encoder, PasswordEncoding},
+    errors::Errors,
+    events::{Event, EventEmitter},
+    fragments::Fragments,
+    mask::Mask,
+    password_finder::{CachedStrategy, Strategy},
+    password_gen::PasswordGenerator,
+    password_reader::{feed_workers, DictionaryCandidates, DictionaryStats, PasswordReader},
+    progress::WorkerPositions,
+    template::Template,
+    verifier::{Verifier, VerifierRegistry},
 };
 
+/// Channels, signals and counters shared by the search and all of its workers.
+#[derive(Clone)]
+pub struct WorkerShared {
+    pub send_password_found: Sender<Result<(String, PasswordEncoding), Errors>>,
+    pub stop_workers_signal: Arc<AtomicBool>,
+    pub cancel_token: CancelToken,
+    /// set by the workers which stopped before the end of their candidates
+    pub interrupted: Arc<AtomicBool>,
+    pub pause_token: PauseToken,
+    pub positions: Arc<WorkerPositions>,
+    pub dictionary_stats: Arc<DictionaryStats>,
+    pub encodings: Vec<PasswordEncoding>,
+    pub emitter: EventEmitter,
+}
+
 pub fn password_check(
     worker_count: usize,
     file_path: &Path,
-    strategy: Strategy,
-    send_password_found: Sender<String>,
-    stop_workers_signal: Arc<AtomicBool>,
-    file_type: Option<Type>,
-    tested_count: Arc<AtomicU64>,
+    strategy: CachedStrategy,
+    mime_type: &str,
+    registry: &VerifierRegistry,
+    shared: &WorkerShared,
 ) -> Result<Vec<JoinHandle<()>>, Errors> {
-    let mut worker_handles = Vec::with_capacity(worker_count);
-
-    for i in 1..=worker_count {
-        let file_path = file_path.clone().to_path_buf();
-        let send_password_found = send_password_found.clone();
-        let stop_workers_signal = stop_workers_signal.clone();
+    // fail before starting any thread
+    let verifier = registry
+        .prepare(mime_type, file_path)
+        .expect("format detected before-hand")?;
+    if !verifier.takes_raw_bytes() {
+        if let Some(&encoding) = shared.encodings.iter().find(|e| !e.is_utf8()) {
+            return Err(Errors::UnsupportedEncoding {
+                encoding,
+                mime_type: mime_type.to_string(),
+            });
+        }
+    }
+    shared.emitter.emit(Event::TargetInfo {
+        mime_type: mime_type.to_string(),
+        description: verifier.describe(),
+    });
+    let mut first_verifier = Some(verifier);
+    // generated once for all workers
+    let candidate_list = strategy.candidate_list()?;
+    let strategy = strategy.strategy;
+    let mut worker_handles = Vec::with_capacity(worker_count + 1);
+    let expansion = strategy.line_expansion()?;
+    // with an expansion, worker `w` of `n` tests the derived candidates `w, w + n...`,
+    // otherwise the lines `w, w + n...`
+    let per_line = match &expansion {
+        // no candidate at all from an empty expansion, but the lines still need a position
+        Some(expansion) => expansion.count()?.max(1) as u64,
+        None => 1,
+    };
+    let line_starts: Vec<u64> = shared
+        .positions
+        .snapshot()
+        .iter()
+        .map(|position| position / per_line)
+        .collect();
+    let broadcast = expansion.is_some();
+    // a single reader hands the dictionary lines out to the workers
+    let mut dictionary_lines = match (&strategy, strategy.dictionaries()?) {
+        (_, Some(dictionaries)) => {
+            let dedup = dictionaries.dedup;
+            let readers =
+                dictionaries.readers(shared.dictionary_stats.clone(), shared.emitter.clone());
+            let (receivers, handle) = feed_workers(readers, dedup, line_starts, broadcast)?;
+            worker_handles.push(handle);
+            receivers
+        }
+        (
+            Strategy::Stream {
+                source, encoding, ..
+            },
+            None,
+        ) => {
+            let reader = PasswordReader::from_reader(BufReader::new(source.take()?), *encoding);
+            let (receivers, handle) =
+                feed_workers(iter::once(Ok(reader)), false, line_starts, broadcast)?;
+            worker_handles.push(handle);
+            receivers
+        }
+        _ => vec![],
+    }
+    .into_iter();
+    let spawned = (1..=worker_count).try_for_each(|i| {
+        let verifier = match first_verifier.take() {
+            Some(verifier) => verifier,
+            None => registry
+                .prepare(mime_type, file_path)
+                .expect("format detected before-hand")?,
+        };
+        let shared = shared.clone();
         // let send_progress_info = send_progress_info.clone();
-        let mut passwords: Passwords = match &strategy {
+        let start = shared.positions.get(i - 1);
+        let passwords: Passwords = match &strategy {
             Strategy::GenPasswords {
                 charsets,
                 min_password_len,
                 max_password_len,
+                skip,
+                limit,
             } => {
                 let c = charsets.clone();
-                let password_gen_worker =
+                let mut password_gen_worker =
                     PasswordGenerator::new(c, *min_password_len, *max_password_len);
+                password_gen_worker.seek(*skip);
+                if let Some(limit) = limit {
+                    password_gen_worker.limit(*limit);
+                }
 
-                Box::new(password_gen_worker)
+                shard(Box::new(password_gen_worker), worker_count, start as usize)
             }
-            Strategy::PasswordFile(password_file_path) => {
-                let password_reader = PasswordReader::new(password_file_path);
-                Box::new(password_reader)
+            Strategy::Mask {
+                mask,
+                custom_charsets,
+                increment,
+            } => {
+                let generator = Mask::parse(mask, custom_charsets)?.generator(*increment)?;
+                shard(Box::new(generator), worker_count, start as usize)
+            }
+            Strategy::Template {
+                template,
+                custom_charsets,
+            } => {
+                let generator = Template::parse(template, custom_charsets)?.generator();
+                shard(Box::new(generator), worker_count, start as usize)
+            }
+            Strategy::Fragments {
+                tokens,
+                min_tokens,
+                max_tokens,
+                separators,
+                cases,
+            } => {
+                let fragments =
+                    Fragments::new(tokens, *min_tokens, *max_tokens, separators, cases)?;
+                shard(
+                    Box::new(fragments.candidates()),
+                    worker_count,
+                    start as usize,
+                )
+            }
+            Strategy::Typos { .. } | Strategy::Dates { .. } => {
+                let list = candidate_list
+                    .as_ref()
+                    .expect("kept in memory for this strategy");
+                shard(list.candidates()?, worker_count, start as usize)
+            }
+            Strategy::PasswordFile { encoding, .. }
+            | Strategy::Dictionaries { encoding, .. }
+            | Strategy::Stream { encoding, .. }
+            | Strategy::Hybrid { encoding, .. }
+            | Strategy::Combinator { encoding, .. } => {
+                // every worker reads every line with an expansion, count them once
+                let stats = if broadcast && i > 1 {
+                    Arc::new(DictionaryStats::default())
+                } else {
+                    shared.dictionary_stats.clone()
+                };
+                let lines = Box::new(DictionaryCandidates::new(
+                    dictionary_lines.next().expect("one receiver per worker"),
+                    *encoding,
+                    stats,
+                ));
+                match &expansion {
+                    Some(expansion) => shard(
+                        expansion.candidates(lines)?,
+                        worker_count,
+                        (start % per_line) as usize,
+                    ),
+                    None => lines,
+                }
             }
         };
-        passwords = filter_for_worker_index(passwords, worker_count, i);
 
-        let tested_count = Arc::clone(&tested_count);
         let join_handle = thread::Builder::new()
             .name(format!("worker-{}", i))
-            .spawn(move || {
-                match file_type {
-                    #[cfg(feature = "rar")]
-                    Some(file) if file.mime_type() == "application/vnd.rar" => {
-                        crate::rar::password_check(
-                            worker_count,
-                            i,
-                            &file_path,
-                            passwords,
-                            send_password_found,
-                            stop_workers_signal,
-                            tested_count,
-                        )
-                    }
-                    Some(file) if file.mime_type() == "application/zip" => {
-                        crate::zip::password_check(
-                            worker_count,
-                            i,
-                            &file_path,
-                            passwords,
-                            send_password_found,
-                            stop_workers_signal,
-                            tested_count,
-                        )
-                    }
-                    #[cfg(feature = "7z")]
-                    Some(file) if file.mime_type() == "application/x-7z-compressed" => {
-                        crate::seven_z::password_check(
-                            worker_count,
-                            i,
-                            &file_path,
-                            passwords,
-                            send_password_found,
-                            stop_workers_signal,
-                            tested_count,
-                        )
-                    }
-                    #[cfg(feature = "pdf")]
-                    Some(file) if file.mime_type() == "application/pdf" => {
-                        crate::pdf::password_check(
-                            worker_count,
-                            i,
-                            &file_path,
-                            passwords,
-                            send_password_found,
-                            stop_workers_signal,
-                            tested_count,
-                        )
-                    }
-                    _ => {
-                        // progress_bar.abandon_with_message(format!(
-                        //     " {} is not supported",
-                        //     file_path.display()
-                        // ));
-                    }
-                }
-            })
-            .unwrap();
+            .spawn(move || check_passwords(verifier, worker_count, i, start, passwords, shared))?;
         worker_handles.push(join_handle);
+        Ok(())
+    });
+    // the receivers not handed out would block the reader
+    drop(dictionary_lines);
+    if let Err(e) = spawned {
+        shared.stop_workers_signal.store(true, Ordering::Relaxed);
+        for handle in worker_handles {
+            let _ = handle.join();
+        }
+        return Err(e);
     }
     Ok(worker_handles)
 }
+
+fn check_passwords(
+    mut verifier: Box<dyn Verifier>,
+    worker_count: usize,
+    worker_index: usize,
+    start: u64,
+    mut passwords: Passwords,
+    shared: WorkerShared,
+) {
+    let batching_dalta = verifier.batch_size();
+    let mut next_position = start;
+    let mut tested = 0;
+    shared.emitter.emit(Event::WorkerStarted {
+        worker: worker_index,
+    });
+
+    let mut processed_delta = 0;
+    // reused for every candidate
+    let mut password = Vec::with_capacity(64);
+    let mut encoder = Encoder::new(shared.encodings.clone());
+    loop {
+        match passwords.next_into(&mut password) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => {
+                let _ = shared.send_password_found.send(Err(e));
+                break;
+            }
+        }
+        let found = if passwords.is_rejected() {
+            // counts as tested, nothing to verify
+            Ok(None)
+        } else {
+            encoder.find(&password, |variant| verifier.verify(variant))
+        };
+        match found {
+            Ok(Some(encoding)) => {
+                let password = String::from_utf8_lossy(&password).into_owned();
+                // Send password and continue processing while waiting for signal
+                if shared
+                    .send_password_found
+                    .send(Ok((password, encoding)))
+                    .is_err()
+                {
+                    // the search is already over
+                    break;
+                }
+            }
+            Ok(None) => (),
+            Err(e) => {
+                // report the failure, the other workers are stopped by the receiving side
+                let _ = shared.send_password_found.send(Err(e));
+                break;
+            }
+        }
+        next_position += worker_count as u64;
+        tested += 1;
+        // own cache line, cheap enough to keep the progress exact
+        shared.positions.set(worker_index - 1, next_position);
+
+        processed_delta += 1;
+        //do not check internal flags too often
+        if processed_delta == batching_dalta {
+            let stopped = || {
+                shared.stop_workers_signal.load(Ordering::Relaxed)
+                    || shared.cancel_token.is_cancelled()
+            };
+            // park here so that no candidate is skipped or tested twice
+            shared.pause_token.wait_while_paused(stopped);
+            if stopped() {
+                shared.interrupted.store(true, Ordering::Relaxed);
+                break;
+            }
+            processed_delta = 0;
+        }
+    }
+    shared.emitter.emit(Event::WorkerFinished {
+        worker: worker_index,
+        tested,
+    });
+}
 #[cfg(test)]
 mod test {
     use std::{process::Command, time::Instant};
@@ -1,2 +1,2 @@
 mod pdf;
-pub use self::pdf::password_check;
+pub use self::pdf::PdfVerifier;
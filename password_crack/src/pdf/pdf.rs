@@ -1,48 +1,22 @@
-use std::{
-    fs::{self},
-    path::Path,
-    sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
-    },
-};
+use std::{fs, path::Path};
 
-use crossbeam_channel::Sender;
+use crate::{errors::Errors, verifier::Verifier};
 
-use crate::Passwords;
-pub fn password_check(
-    worker_count: usize,
-    worker_index: usize,
-    pdf_file: &Path,
-    passwords: Passwords,
-    send_password_found: Sender<String>,
-    stop_workers_signal: Arc<AtomicBool>,
-    tested_count: Arc<AtomicU64>,
-) {
-    let batching_dalta = worker_count * 500;
-    let first_worker = worker_index == 1;
-    let progress_bar_delta: u64 = (batching_dalta * worker_count) as u64;
-    let buffer = fs::read(pdf_file).unwrap();
-
-    let mut processed_delta = 0;
-    for password in passwords {
-        let res = pdf::file::File::from_data_password(&buffer as &[u8], password.as_bytes());
-        if res.is_ok() {
-            send_password_found
-                .send(password)
-                .expect("Send found password should not fail");
+pub struct PdfVerifier {
+    buffer: Vec<u8>,
+}
+impl Verifier for PdfVerifier {
+    fn prepare(pdf_file: &Path) -> Result<Self, Errors> {
+        let buffer = fs::read(pdf_file)?;
+        // a document opening with an empty password has nothing to find
+        if pdf::file::File::from_data_password(&buffer as &[u8], b"").is_ok() {
+            return Err(Errors::NotEncrypted);
         }
+        Ok(PdfVerifier { buffer })
+    }
 
-        processed_delta += 1;
-        //do not check internal flags too often
-        if processed_delta == batching_dalta {
-            if first_worker {
-                tested_count.fetch_add(progress_bar_delta, Ordering::SeqCst);
-            }
-            if stop_workers_signal.load(Ordering::Relaxed) {
-                break;
-            }
-            processed_delta = 0;
-        }
+    fn verify(&mut self, password: &[u8]) -> Result<bool, Errors> {
+        let res = pdf::file::File::from_data_password(&self.buffer as &[u8], password);
+        Ok(res.is_ok())
     }
 }
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam_utils::CachePadded;
+
+/// Position of every worker in the candidate stream of a search.
+///
+/// Worker `w` of `n` tests the indices `w, w + n, w + 2n...` and owns one counter holding
+/// the index of its next candidate. Workers update their counter after every candidate,
+/// so each one sits on its own cache line.
+#[derive(Debug)]
+pub struct WorkerPositions {
+    positions: Vec<CachePadded<AtomicU64>>,
+}
+impl WorkerPositions {
+    /// Positions of a search that has not started yet.
+    pub fn new(workers: usize) -> Self {
+        WorkerPositions::from_positions(&(0..workers as u64).collect::<Vec<_>>())
+    }
+    pub fn from_positions(positions: &[u64]) -> Self {
+        WorkerPositions {
+            positions: positions
+                .iter()
+                .map(|&p| CachePadded::new(AtomicU64::new(p)))
+                .collect(),
+        }
+    }
+    pub fn get(&self, worker: usize) -> u64 {
+        self.positions[worker].load(Ordering::Relaxed)
+    }
+    pub fn set(&self, worker: usize, position: u64) {
+        self.positions[worker].store(position, Ordering::Relaxed)
+    }
+    pub fn snapshot(&self) -> Vec<u64> {
+        self.positions
+            .iter()
+            .map(|p| p.load(Ordering::Relaxed))
+            .collect()
+    }
+    /// Number of candidates already tested by all workers.
+    pub fn tested_count(&self) -> u64 {
+        tested_count(&self.snapshot())
+    }
+}
+
+pub fn tested_count(positions: &[u64]) -> u64 {
+    let workers = positions.len() as u64;
+    positions
+        .iter()
+        .enumerate()
+        .map(|(worker, position)| position.saturating_sub(worker as u64) / workers)
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::WorkerPositions;
+
+    #[test]
+    fn count_from_positions() {
+        let positions = WorkerPositions::new(3);
+        assert_eq!(positions.tested_count(), 0);
+        // worker 0 tested 0, 3, 6; worker 1 tested 1; worker 2 tested 2, 5
+        positions.set(0, 9);
+        positions.set(1, 4);
+        positions.set(2, 8);
+        assert_eq!(positions.tested_count(), 6);
+        assert_eq!(positions.snapshot(), vec![9, 4, 8]);
+    }
+}
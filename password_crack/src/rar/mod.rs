@@ -1,2 +1,2 @@
 mod rar;
-pub use self::rar::password_check;
+pub use self::rar::RarVerifier;
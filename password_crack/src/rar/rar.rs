@@ -1,48 +1,52 @@
 use std::{
-    path::Path,
-    sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
-    },
+    fs::File,
+    path::{Path, PathBuf},
 };
 
-use crossbeam_channel::Sender;
-use unrar::Archive;
+use unrar::{error::Code, Archive};
 
-use crate::Passwords;
-pub fn password_check(
-    worker_count: usize,
-    worker_index: usize,
-    rar_file: &Path,
-    passwords: Passwords,
-    send_password_found: Sender<String>,
-    stop_workers_signal: Arc<AtomicBool>,
-    tested_count: Arc<AtomicU64>,
-) {
-    let batching_dalta = worker_count * 500;
-    let first_worker = worker_index == 1;
-    let progress_bar_delta: u64 = (batching_dalta * worker_count) as u64;
+use crate::{errors::Errors, verifier::Verifier};
 
-    let mut processed_delta = 0;
-    for password in passwords {
-        let archive = Archive::with_password(rar_file.display().to_string(), password.to_string());
-        let mut open_archive = archive.test().unwrap();
-        if open_archive.process().is_ok() {
-            send_password_found
-                .send(password)
-                .expect("Send found password should not fail");
-        }
+pub struct RarVerifier {
+    rar_file: PathBuf,
+}
+impl Verifier for RarVerifier {
+    fn prepare(rar_file: &Path) -> Result<Self, Errors> {
+        File::open(rar_file)?;
+        Ok(RarVerifier {
+            rar_file: rar_file.to_path_buf(),
+        })
+    }
 
-        processed_delta += 1;
-        //do not check internal flags too often
-        if processed_delta == batching_dalta {
-            if first_worker {
-                tested_count.fetch_add(progress_bar_delta, Ordering::SeqCst);
+    fn verify(&mut self, password: &[u8]) -> Result<bool, Errors> {
+        let archive = Archive::with_password(
+            self.rar_file.display().to_string(),
+            String::from_utf8_lossy(password).to_string(),
+        );
+        let mut open_archive = match archive.test() {
+            Ok(open_archive) => open_archive,
+            // archives with encrypted headers already check the password when opening
+            Err(e) if e.code == Code::BadPassword || e.code == Code::MissingPassword => {
+                return Ok(false)
             }
-            if stop_workers_signal.load(Ordering::Relaxed) {
-                break;
+            Err(e) => {
+                return Err(Errors::CorruptArchive {
+                    message: e.to_string(),
+                })
             }
-            processed_delta = 0;
+        };
+        match open_archive.process() {
+            Ok(_) => Ok(true),
+            // a wrong password fails the checksum of the extracted data on older archives
+            Err(e) if e.code == Code::BadPassword || e.code == Code::BadData => Ok(false),
+            Err(e) => Err(Errors::CorruptArchive {
+                message: e.to_string(),
+            }),
         }
     }
+
+    // unrar only takes the password as a string
+    fn takes_raw_bytes(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,557 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use crate::{
+    candidates::{Candidates, Passwords},
+    errors::Errors,
+};
+
+/// One function of a rule, in the hashcat rule syntax.
+///
+/// Positions `N` and `M` are `0-9` then `A-Z` for 10 to 35, `X` and `Y` are single bytes.
+/// Functions working on a position out of the word leave it unchanged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Function {
+    /// `:`
+    Nothing,
+    /// `l`
+    Lowercase,
+    /// `u`
+    Uppercase,
+    /// `c`
+    Capitalize,
+    /// `C`
+    InvertCapitalize,
+    /// `t`
+    ToggleCase,
+    /// `TN`
+    ToggleAt(usize),
+    /// `r`
+    Reverse,
+    /// `d`
+    Duplicate,
+    /// `pN`, appends the word `N` times
+    DuplicateTimes(usize),
+    /// `f`, appends the reversed word
+    Reflect,
+    /// `{`
+    RotateLeft,
+    /// `}`
+    RotateRight,
+    /// `$X`
+    Append(u8),
+    /// `^X`
+    Prepend(u8),
+    /// `[`
+    DeleteFirst,
+    /// `]`
+    DeleteLast,
+    /// `DN`
+    DeleteAt(usize),
+    /// `xNM`, keeps `M` bytes from `N`
+    Extract(usize, usize),
+    /// `ONM`, deletes `M` bytes from `N`
+    Omit(usize, usize),
+    /// `iNX`
+    Insert(usize, u8),
+    /// `oNX`
+    Overwrite(usize, u8),
+    /// `'N`
+    Truncate(usize),
+    /// `sXY`, e.g. `sa@` for leetspeak
+    Replace(u8, u8),
+    /// `@X`
+    Purge(u8),
+    /// `zN`
+    DuplicateFirst(usize),
+    /// `ZN`
+    DuplicateLast(usize),
+    /// `q`
+    DuplicateAll,
+    /// `k`
+    SwapFront,
+    /// `K`
+    SwapBack,
+    /// `*NM`
+    Swap(usize, usize),
+    /// `LN`
+    ShiftLeft(usize),
+    /// `RN`
+    ShiftRight(usize),
+    /// `+N`
+    Increment(usize),
+    /// `-N`
+    Decrement(usize),
+    /// `.N`, replaces the byte at `N` with the next one
+    ReplaceNext(usize),
+    /// `,N`, replaces the byte at `N` with the previous one
+    ReplacePrevious(usize),
+    /// `yN`, prepends the first `N` bytes
+    DuplicateBlockFront(usize),
+    /// `YN`, appends the last `N` bytes
+    DuplicateBlockBack(usize),
+    /// `E`
+    Title,
+    /// `eX`, title case with `X` as separator
+    TitleSeparator(u8),
+    /// `<N`, rejects words longer than `N`
+    RejectLonger(usize),
+    /// `>N`, rejects words shorter than `N`
+    RejectShorter(usize),
+    /// `_N`, rejects words not of length `N`
+    RejectLengthNot(usize),
+    /// `!X`
+    RejectContains(u8),
+    /// `/X`
+    RejectNotContains(u8),
+    /// `(X`
+    RejectFirstNot(u8),
+    /// `)X`
+    RejectLastNot(u8),
+    /// `=NX`
+    RejectAtNot(usize, u8),
+    /// `%NX`, rejects words with fewer than `N` times `X`
+    RejectFewer(usize, u8),
+}
+impl Function {
+    /// Applies the function to `word`, returns `false` if the word is rejected.
+    fn apply(self, word: &mut Vec<u8>) -> bool {
+        let len = word.len();
+        match self {
+            Function::Nothing => (),
+            Function::Lowercase => word.make_ascii_lowercase(),
+            Function::Uppercase => word.make_ascii_uppercase(),
+            Function::Capitalize => {
+                word.make_ascii_lowercase();
+                if let Some(first) = word.first_mut() {
+                    first.make_ascii_uppercase();
+                }
+            }
+            Function::InvertCapitalize => {
+                word.make_ascii_uppercase();
+                if let Some(first) = word.first_mut() {
+                    first.make_ascii_lowercase();
+                }
+            }
+            Function::ToggleCase => word.iter_mut().for_each(toggle),
+            Function::ToggleAt(n) => {
+                if let Some(b) = word.get_mut(n) {
+                    toggle(b);
+                }
+            }
+            Function::Reverse => word.reverse(),
+            Function::Duplicate => word.extend_from_within(..),
+            Function::DuplicateTimes(n) => {
+                for _ in 0..n {
+                    word.extend_from_within(..len);
+                }
+            }
+            Function::Reflect => {
+                word.extend_from_within(..);
+                word[len..].reverse();
+            }
+            Function::RotateLeft if len > 0 => word.rotate_left(1),
+            Function::RotateRight if len > 0 => word.rotate_right(1),
+            Function::Append(x) => word.push(x),
+            Function::Prepend(x) => word.insert(0, x),
+            Function::DeleteFirst if len > 0 => {
+                word.remove(0);
+            }
+            Function::DeleteLast => {
+                word.pop();
+            }
+            Function::DeleteAt(n) if n < len => {
+                word.remove(n);
+            }
+            Function::Extract(n, m) if n + m <= len => {
+                word.truncate(n + m);
+                word.drain(..n);
+            }
+            Function::Omit(n, m) if n + m <= len => {
+                word.drain(n..n + m);
+            }
+            Function::Insert(n, x) if n <= len => word.insert(n, x),
+            Function::Overwrite(n, x) if n < len => word[n] = x,
+            Function::Truncate(n) => word.truncate(n),
+            Function::Replace(x, y) => word.iter_mut().filter(|b| **b == x).for_each(|b| *b = y),
+            Function::Purge(x) => word.retain(|&b| b != x),
+            Function::DuplicateFirst(n) if len > 0 => {
+                word.resize(len + n, word[0]);
+                word.rotate_right(n);
+            }
+            Function::DuplicateLast(n) if len > 0 => word.resize(len + n, word[len - 1]),
+            Function::DuplicateAll => {
+                word.resize(len * 2, 0);
+                for i in (0..len).rev() {
+                    word[2 * i] = word[i];
+                    word[2 * i + 1] = word[i];
+                }
+            }
+            Function::SwapFront if len > 1 => word.swap(0, 1),
+            Function::SwapBack if len > 1 => word.swap(len - 2, len - 1),
+            Function::Swap(n, m) if n < len && m < len => word.swap(n, m),
+            Function::ShiftLeft(n) if n < len => word[n] <<= 1,
+            Function::ShiftRight(n) if n < len => word[n] >>= 1,
+            Function::Increment(n) if n < len => word[n] = word[n].wrapping_add(1),
+            Function::Decrement(n) if n < len => word[n] = word[n].wrapping_sub(1),
+            Function::ReplaceNext(n) if n + 1 < len => word[n] = word[n + 1],
+            Function::ReplacePrevious(n) if n > 0 && n < len => word[n] = word[n - 1],
+            Function::DuplicateBlockFront(n) if n <= len => {
+                word.extend_from_within(..n);
+                word.rotate_right(n);
+            }
+            Function::DuplicateBlockBack(n) if n <= len => word.extend_from_within(len - n..),
+            Function::Title => title(word, b' '),
+            Function::TitleSeparator(x) => title(word, x),
+            Function::RejectLonger(n) => return len <= n,
+            Function::RejectShorter(n) => return len >= n,
+            Function::RejectLengthNot(n) => return len == n,
+            Function::RejectContains(x) => return !word.contains(&x),
+            Function::RejectNotContains(x) => return word.contains(&x),
+            Function::RejectFirstNot(x) => return word.first() == Some(&x),
+            Function::RejectLastNot(x) => return word.last() == Some(&x),
+            Function::RejectAtNot(n, x) => return word.get(n) == Some(&x),
+            Function::RejectFewer(n, x) => return word.iter().filter(|&&b| b == x).count() >= n,
+            // position out of the word
+            _ => (),
+        }
+        true
+    }
+}
+
+fn toggle(b: &mut u8) {
+    if b.is_ascii_lowercase() {
+        b.make_ascii_uppercase()
+    } else {
+        b.make_ascii_lowercase()
+    }
+}
+
+fn title(word: &mut [u8], separator: u8) {
+    word.make_ascii_lowercase();
+    let mut start = true;
+    for b in word.iter_mut() {
+        if start {
+            b.make_ascii_uppercase();
+        }
+        start = *b == separator;
+    }
+}
+
+/// Sequence of functions turning a dictionary word into a candidate, e.g. `c $1 $2`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    functions: Vec<Function>,
+}
+impl Rule {
+    /// Parses one line of a rule file, spaces between functions are ignored.
+    pub fn parse(rule: &[u8]) -> Result<Self, String> {
+        let mut functions = vec![];
+        let mut bytes = rule.iter().copied();
+        while let Some(name) = bytes.next() {
+            let mut byte = || {
+                bytes
+                    .next()
+                    .ok_or_else(|| format!("'{}' is missing an argument", char::from(name)))
+            };
+            let function = match name {
+                b' ' | b'\t' => continue,
+                b':' => Function::Nothing,
+                b'l' => Function::Lowercase,
+                b'u' => Function::Uppercase,
+                b'c' => Function::Capitalize,
+                b'C' => Function::InvertCapitalize,
+                b't' => Function::ToggleCase,
+                b'T' => Function::ToggleAt(position(byte()?)?),
+                b'r' => Function::Reverse,
+                b'd' => Function::Duplicate,
+                b'p' => Function::DuplicateTimes(position(byte()?)?),
+                b'f' => Function::Reflect,
+                b'{' => Function::RotateLeft,
+                b'}' => Function::RotateRight,
+                b'$' => Function::Append(byte()?),
+                b'^' => Function::Prepend(byte()?),
+                b'[' => Function::DeleteFirst,
+                b']' => Function::DeleteLast,
+                b'D' => Function::DeleteAt(position(byte()?)?),
+                b'x' => Function::Extract(position(byte()?)?, position(byte()?)?),
+                b'O' => Function::Omit(position(byte()?)?, position(byte()?)?),
+                b'i' => Function::Insert(position(byte()?)?, byte()?),
+                b'o' => Function::Overwrite(position(byte()?)?, byte()?),
+                b'\'' => Function::Truncate(position(byte()?)?),
+                b's' => Function::Replace(byte()?, byte()?),
+                b'@' => Function::Purge(byte()?),
+                b'z' => Function::DuplicateFirst(position(byte()?)?),
+                b'Z' => Function::DuplicateLast(position(byte()?)?),
+                b'q' => Function::DuplicateAll,
+                b'k' => Function::SwapFront,
+                b'K' => Function::SwapBack,
+                b'*' => Function::Swap(position(byte()?)?, position(byte()?)?),
+                b'L' => Function::ShiftLeft(position(byte()?)?),
+                b'R' => Function::ShiftRight(position(byte()?)?),
+                b'+' => Function::Increment(position(byte()?)?),
+                b'-' => Function::Decrement(position(byte()?)?),
+                b'.' => Function::ReplaceNext(position(byte()?)?),
+                b',' => Function::ReplacePrevious(position(byte()?)?),
+                b'y' => Function::DuplicateBlockFront(position(byte()?)?),
+                b'Y' => Function::DuplicateBlockBack(position(byte()?)?),
+                b'E' => Function::Title,
+                b'e' => Function::TitleSeparator(byte()?),
+                b'<' => Function::RejectLonger(position(byte()?)?),
+                b'>' => Function::RejectShorter(position(byte()?)?),
+                b'_' => Function::RejectLengthNot(position(byte()?)?),
+                b'!' => Function::RejectContains(byte()?),
+                b'/' => Function::RejectNotContains(byte()?),
+                b'(' => Function::RejectFirstNot(byte()?),
+                b')' => Function::RejectLastNot(byte()?),
+                b'=' => Function::RejectAtNot(position(byte()?)?, byte()?),
+                b'%' => Function::RejectFewer(position(byte()?)?, byte()?),
+                other => return Err(format!("unsupported function '{}'", char::from(other))),
+            };
+            functions.push(function);
+        }
+        Ok(Rule { functions })
+    }
+
+    /// Replaces the content of `candidate` with `word` transformed by the rule,
+    /// returns `false` if the rule rejects the word.
+    pub fn apply(&self, word: &[u8], candidate: &mut Vec<u8>) -> bool {
+        candidate.clear();
+        candidate.extend_from_slice(word);
+        self.functions.iter().all(|f| f.apply(candidate))
+    }
+}
+
+fn position(byte: u8) -> Result<usize, String> {
+    match byte {
+        b'0'..=b'9' => Ok((byte - b'0') as usize),
+        b'A'..=b'Z' => Ok((byte - b'A') as usize + 10),
+        _ => Err(format!("'{}' is not a position", char::from(byte))),
+    }
+}
+
+/// Rules of one or more rule files, every dictionary word is tried with each of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+impl Rules {
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn parse(content: &[u8]) -> Result<Self, Errors> {
+        let mut rules = vec![];
+        for (i, line) in content.split(|&b| b == b'\n').enumerate() {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+            let rule = Rule::parse(line)
+                .map_err(|e| Errors::invalid_rule_error(format!("line {}: {}", i + 1, e)))?;
+            rules.push(rule);
+        }
+        Ok(Rules { rules })
+    }
+
+    /// Rules of all `paths`, one file after the other.
+    pub fn load(paths: &[PathBuf]) -> Result<Self, Errors> {
+        let mut rules = vec![];
+        for path in paths {
+            let content = fs::read(path)?;
+            let file_rules = Rules::parse(&content).map_err(|e| match e {
+                Errors::InvalidRule { message } => {
+                    Errors::invalid_rule_error(format!("{} {}", path.display(), message))
+                }
+                e => e,
+            })?;
+            rules.extend(file_rules.rules);
+        }
+        if rules.is_empty() {
+            return Err(Errors::invalid_rule_error("no rule found".to_string()));
+        }
+        Ok(Rules { rules })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// Every word of `words` transformed by every rule, the rules of a word come one after the other.
+pub struct RuleCandidates {
+    words: Passwords,
+    rules: Arc<Rules>,
+    word: Vec<u8>,
+    /// rule of the next candidate, `rules.len()` to read the next word first
+    next_rule: usize,
+    rejected: bool,
+}
+impl RuleCandidates {
+    pub fn new(words: Passwords, rules: Arc<Rules>) -> Self {
+        let next_rule = rules.len();
+        RuleCandidates {
+            words,
+            rules,
+            word: Vec::new(),
+            next_rule,
+            rejected: false,
+        }
+    }
+}
+impl Candidates for RuleCandidates {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next_rule == self.rules.len() {
+            if !self.words.next_into(&mut self.word)? {
+                return Ok(false);
+            }
+            self.next_rule = 0;
+        }
+        self.rejected = !self.rules.rules[self.next_rule].apply(&self.word, buffer);
+        self.next_rule += 1;
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        let rule_count = self.rules.len();
+        let left = rule_count - self.next_rule;
+        if n <= left {
+            self.next_rule += n;
+            return Ok(true);
+        }
+        // skip whole words without applying their rules
+        let n = n - left;
+        self.next_rule = rule_count;
+        if !self.words.advance_by(n / rule_count)? {
+            return Ok(false);
+        }
+        if n % rule_count > 0 {
+            if !self.words.next_into(&mut self.word)? {
+                return Ok(false);
+            }
+            self.next_rule = n % rule_count;
+        }
+        Ok(true)
+    }
+
+    fn is_rejected(&self) -> bool {
+        self.rejected
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{Rule, RuleCandidates, Rules};
+    use crate::{
+        candidates::test::{assert_shards_match, collect, words},
+        Errors,
+    };
+
+    fn apply(rule: &str, word: &str) -> Option<String> {
+        let mut candidate = Vec::new();
+        Rule::parse(rule.as_bytes())
+            .unwrap()
+            .apply(word.as_bytes(), &mut candidate)
+            .then(|| String::from_utf8(candidate).unwrap())
+    }
+
+    #[test]
+    fn functions() {
+        let cases = [
+            (":", "p@ssW0rd", "p@ssW0rd"),
+            ("l", "p@ssW0rd", "p@ssw0rd"),
+            ("u", "p@ssW0rd", "P@SSW0RD"),
+            ("c", "p@ssW0rd", "P@ssw0rd"),
+            ("C", "p@ssW0rd", "p@SSW0RD"),
+            ("t", "p@ssW0rd", "P@SSw0RD"),
+            ("T3", "p@ssW0rd", "p@sSW0rd"),
+            ("r", "p@ssW0rd", "dr0Wss@p"),
+            ("d", "p@ss", "p@ssp@ss"),
+            ("p2", "ab", "ababab"),
+            ("f", "abc", "abccba"),
+            ("{", "abc", "bca"),
+            ("}", "abc", "cab"),
+            ("$1 $2", "abc", "abc12"),
+            ("^1^2", "abc", "21abc"),
+            ("[", "abc", "bc"),
+            ("]", "abc", "ab"),
+            ("D1", "abc", "ac"),
+            ("x13", "abcdef", "bcd"),
+            ("O12", "abcdef", "adef"),
+            ("i1X", "abc", "aXbc"),
+            ("o1X", "abc", "aXc"),
+            ("'2", "abc", "ab"),
+            ("sa@so0", "bazooka", "b@z00k@"),
+            ("@a", "banana", "bnn"),
+            ("z2", "abc", "aaabc"),
+            ("Z2", "abc", "abccc"),
+            ("q", "abc", "aabbcc"),
+            ("k", "abc", "bac"),
+            ("K", "abc", "acb"),
+            ("*02", "abc", "cba"),
+            ("+0", "abc", "bbc"),
+            ("-1", "abc", "aac"),
+            (".0", "abc", "bbc"),
+            (",1", "abc", "aac"),
+            ("L0", "\u{20}", "@"),
+            ("R0", "@", "\u{20}"),
+            ("y2", "abc", "ababc"),
+            ("Y2", "abc", "abcbc"),
+            ("E", "hello wORLD", "Hello World"),
+            ("e-", "jean-luc", "Jean-Luc"),
+            // positions out of the word leave it unchanged
+            ("D9", "abc", "abc"),
+            ("x25", "abc", "abc"),
+            ("oAx", "abc", "abc"),
+        ];
+        for (rule, word, expected) in cases {
+            assert_eq!(apply(rule, word).as_deref(), Some(expected), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn reject() {
+        assert_eq!(apply("<3", "abcd"), None);
+        assert_eq!(apply("<4 $1", "abcd").as_deref(), Some("abcd1"));
+        assert_eq!(apply(">5", "abcd"), None);
+        assert_eq!(apply("_4", "abcd").as_deref(), Some("abcd"));
+        assert_eq!(apply("!b", "abcd"), None);
+        assert_eq!(apply("/z", "abcd"), None);
+        assert_eq!(apply("(a)d", "abcd").as_deref(), Some("abcd"));
+        assert_eq!(apply("=1c", "abcd"), None);
+        assert_eq!(apply("%2a", "abca").as_deref(), Some("abca"));
+        // rejection applies to the word transformed so far
+        assert_eq!(apply("$1 <4", "abc").as_deref(), Some("abc1"));
+    }
+
+    #[test]
+    fn parse_rule_file() {
+        let rules = Rules::parse(b"# best64 excerpt\n:\r\nr\n\nu\n$1 $2\n").unwrap();
+        assert_eq!(rules.len(), 4);
+        let error = Rules::parse(b"c\n$\n").unwrap_err();
+        assert!(matches!(error, Errors::InvalidRule { message } if message.starts_with("line 2")));
+        assert!(Rules::parse(b"M").is_err());
+        assert!(Rules::parse(b"Ta").is_err());
+    }
+
+    #[test]
+    fn words_times_rules() {
+        let rules = Arc::new(Rules::parse(b":\nu\n$1\n>3").unwrap());
+        let expected = [
+            "ab", "AB", "ab1", "", "abc", "ABC", "abc1", "abc", "x", "X", "x1", "",
+        ];
+        let candidates = || {
+            Box::new(RuleCandidates::new(
+                words(&["ab", "abc", "x"]),
+                rules.clone(),
+            )) as _
+        };
+        // rejected candidates are collected as empty strings
+        assert_eq!(collect(candidates()), expected);
+        for workers in 1..=5 {
+            assert_shards_match(candidates, workers);
+        }
+    }
+}
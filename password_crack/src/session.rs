@@ -0,0 +1,123 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    encodings::PasswordEncoding, errors::Errors, password_finder::Strategy, progress,
+};
+
+const SESSION_VERSION: u32 = 1;
+
+/// Snapshot of a search, enough to continue it after a restart with [`crate::Cracker::restore`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    version: u32,
+    pub file_path: String,
+    /// SHA-1 of the target file, a session only applies to the file it was created for
+    pub file_hash: String,
+    pub strategy: Strategy,
+    pub workers: usize,
+    /// Per worker, index of the next candidate to test in the strategy's candidate stream.
+    /// Worker `i` tests the indices `i, i + workers, i + 2 * workers...`
+    pub positions: Vec<u64>,
+    #[serde(default = "default_encodings")]
+    pub encodings: Vec<PasswordEncoding>,
+}
+impl Session {
+    pub fn new(
+        file_path: String,
+        file_hash: String,
+        strategy: Strategy,
+        positions: Vec<u64>,
+        encodings: Vec<PasswordEncoding>,
+    ) -> Self {
+        Session {
+            version: SESSION_VERSION,
+            file_path,
+            file_hash,
+            strategy,
+            workers: positions.len(),
+            positions,
+            encodings,
+        }
+    }
+    pub fn load(session_path: &Path) -> Result<Self, Errors> {
+        let session: Session = serde_json::from_slice(&fs::read(session_path)?)
+            .map_err(|e| Errors::invalid_session_error(e.to_string()))?;
+        if session.version != SESSION_VERSION {
+            return Err(Errors::invalid_session_error(format!(
+                "unsupported version {}",
+                session.version
+            )));
+        }
+        if session.workers == 0 || session.positions.len() != session.workers {
+            return Err(Errors::invalid_session_error(
+                "worker positions do not match the worker count".to_string(),
+            ));
+        }
+        Ok(session)
+    }
+    /// Writes the session atomically so that a crash while saving keeps the previous checkpoint.
+    pub fn save(&self, session_path: &Path) -> Result<(), Errors> {
+        let content =
+            serde_json::to_vec_pretty(self).map_err(|e| Errors::invalid_session_error(e.to_string()))?;
+        let tmp_path = session_path.with_extension("tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, session_path)?;
+        Ok(())
+    }
+    /// Number of candidates already tested by all workers.
+    pub fn tested_count(&self) -> u64 {
+        progress::tested_count(&self.positions)
+    }
+}
+
+fn default_encodings() -> Vec<PasswordEncoding> {
+    vec![PasswordEncoding::Utf8]
+}
+
+pub fn file_hash(file_path: &Path) -> Result<String, Errors> {
+    let mut file = File::open(file_path)?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Session;
+    use crate::{PasswordEncoding, Strategy};
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("password_crack_save_and_load.session");
+        let session = Session::new(
+            "archive.zip".to_string(),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string(),
+            Strategy::GenPasswords {
+                charsets: vec!['a', 'b', ','],
+                min_password_len: 1,
+                max_password_len: 3,
+                skip: 0,
+                limit: None,
+            },
+            vec![12, 10, 14],
+            vec![PasswordEncoding::Utf8, PasswordEncoding::Cp936],
+        );
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, session);
+        // worker 0 tested 0, 3, 6, 9; worker 1 tested 1, 4, 7; worker 2 tested 2, 5, 8, 11
+        assert_eq!(loaded.tested_count(), 11);
+    }
+}
@@ -1,2 +1,2 @@
 mod seven_z;
-pub use self::seven_z::password_check;
+pub use self::seven_z::SevenZVerifier;
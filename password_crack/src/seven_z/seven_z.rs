@@ -1,50 +1,38 @@
 use std::{
-    path::Path,
-    sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
-    },
+    fs::File,
+    path::{Path, PathBuf},
 };
 
-use crossbeam_channel::Sender;
+use crate::{errors::Errors, verifier::Verifier};
 
-use crate::Passwords;
-pub fn password_check(
-    worker_count: usize,
-    worker_index: usize,
-    senven_z_file: &Path,
-    passwords: Passwords,
-    send_password_found: Sender<String>,
-    stop_workers_signal: Arc<AtomicBool>,
-    tested_count: Arc<AtomicU64>,
-) {
-    let batching_dalta = worker_count * 10;
-    let first_worker = worker_index == 1;
-    let progress_bar_delta: u64 = (batching_dalta * worker_count) as u64;
-    let mut processed_delta = 0;
-    for password in passwords {
+pub struct SevenZVerifier {
+    senven_z_file: PathBuf,
+}
+impl Verifier for SevenZVerifier {
+    fn prepare(senven_z_file: &Path) -> Result<Self, Errors> {
+        // surface I/O problems now, decompression errors are indistinguishable from a wrong password
+        File::open(senven_z_file)?;
+        Ok(SevenZVerifier {
+            senven_z_file: senven_z_file.to_path_buf(),
+        })
+    }
+
+    fn verify(&mut self, password: &[u8]) -> Result<bool, Errors> {
         let res = sevenz_rust::decompress_file_with_password(
-            &senven_z_file,
+            &self.senven_z_file,
             "test/",
-            password.as_str().into(),
+            (&*String::from_utf8_lossy(password)).into(),
         );
-        match res {
-            Ok(()) => send_password_found
-                .send(password)
-                .expect("Send found password should not fail"),
-            _ => {}
-        }
+        Ok(res.is_ok())
+    }
+
+    // decompressing the whole archive is slow, check the stop signal more often
+    fn batch_size(&self) -> usize {
+        10
+    }
 
-        processed_delta += 1;
-        //do not check internal flags too often
-        if processed_delta == batching_dalta {
-            if first_worker {
-                tested_count.fetch_add(progress_bar_delta, Ordering::SeqCst);
-            }
-            if stop_workers_signal.load(Ordering::Relaxed) {
-                break;
-            }
-            processed_delta = 0;
-        }
+    // the password is passed as a string
+    fn takes_raw_bytes(&self) -> bool {
+        false
     }
 }
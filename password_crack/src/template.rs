@@ -0,0 +1,350 @@
+use std::{io::Write, sync::Arc};
+
+use crate::{candidates::Candidates, errors::Errors, mask::Mask};
+
+/// What a user remembers of a password written down in one line, e.g.
+/// `zhang{1985..1995}{?d?d}!` or `Pa{s,$}{s,$}w0rd`.
+///
+/// `{a,b}` is any of its comma separated alternatives, which may be empty or contain
+/// placeholders and other groups, `{1985..1995}` any number of the range, padded with zeros
+/// like its bounds, and `?d` any character of a charset like in a [`crate::Mask`].
+/// `\` makes the next character stand for itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+    /// per part, number of candidates of the parts after it
+    strides: Vec<usize>,
+    count: usize,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    /// literal text or charset
+    Choices(Vec<Vec<u8>>),
+    /// numbers from `start` to `end`, decreasing if `end` is lower, padded with zeros to `width`
+    Range { start: u64, end: u64, width: usize },
+    Group {
+        alternatives: Vec<Template>,
+        count: usize,
+    },
+}
+impl Part {
+    fn count(&self) -> usize {
+        match self {
+            Part::Choices(choices) => choices.len(),
+            // checked when parsing
+            Part::Range { start, end, .. } => start.abs_diff(*end) as usize + 1,
+            Part::Group { count, .. } => *count,
+        }
+    }
+    fn write(&self, mut index: usize, buffer: &mut Vec<u8>) {
+        match self {
+            Part::Choices(choices) => buffer.extend_from_slice(&choices[index]),
+            Part::Range { start, end, width } => {
+                let number = if start <= end {
+                    start + index as u64
+                } else {
+                    start - index as u64
+                };
+                let _ = write!(buffer, "{:0width$}", number, width = width);
+            }
+            Part::Group { alternatives, .. } => {
+                for alternative in alternatives {
+                    if index < alternative.count {
+                        alternative.write(index, buffer);
+                        return;
+                    }
+                    index -= alternative.count;
+                }
+            }
+        }
+    }
+}
+impl Template {
+    pub fn parse(template: &str, custom_charsets: &[String]) -> Result<Self, Errors> {
+        if template.is_empty() {
+            return Err(Errors::invalid_template_error(
+                "the template is empty".to_string(),
+            ));
+        }
+        parse_sequence(template, custom_charsets)
+    }
+
+    /// Number of passwords matching the template, some of which may be identical.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn generator(self) -> TemplateGenerator {
+        TemplateGenerator {
+            template: Arc::new(self),
+            next: 0,
+        }
+    }
+
+    fn new(parts: Vec<Part>) -> Result<Self, Errors> {
+        let overflow = || Errors::MathError {
+            message: String::from("算术溢出"),
+        };
+        let mut strides = vec![1; parts.len()];
+        let mut count: usize = 1;
+        for (part, stride) in parts.iter().zip(strides.iter_mut()).rev() {
+            *stride = count;
+            count = count.checked_mul(part.count()).ok_or_else(overflow)?;
+        }
+        Ok(Template {
+            parts,
+            strides,
+            count,
+        })
+    }
+
+    /// Appends the password at `index`, lower than `count`, the last part changing first.
+    fn write(&self, index: usize, buffer: &mut Vec<u8>) {
+        for (part, stride) in self.parts.iter().zip(&self.strides) {
+            part.write(index / stride % part.count(), buffer);
+        }
+    }
+}
+
+fn parse_sequence(text: &str, custom_charsets: &[String]) -> Result<Template, Errors> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let part = match c {
+            '\\' => match chars.next() {
+                Some(c) => {
+                    literal.push(c);
+                    continue;
+                }
+                None => {
+                    return Err(Errors::invalid_template_error(
+                        "nothing to escape after the last '\\'".to_string(),
+                    ))
+                }
+            },
+            '?' => {
+                let placeholder = match chars.next() {
+                    Some('?') => {
+                        literal.push('?');
+                        continue;
+                    }
+                    Some(c) => format!("?{}", c),
+                    None => "?".to_string(),
+                };
+                let mask = Mask::parse(&placeholder, custom_charsets).map_err(|e| match e {
+                    Errors::InvalidMask { message } => Errors::invalid_template_error(message),
+                    e => e,
+                })?;
+                Part::Choices(
+                    mask.positions()[0]
+                        .iter()
+                        .map(|c| c.to_string().into_bytes())
+                        .collect(),
+                )
+            }
+            '{' => {
+                let group = take_group(&mut chars)?;
+                match parse_range(&group) {
+                    Some(range) => range?,
+                    None => {
+                        let alternatives = split_alternatives(&group)
+                            .into_iter()
+                            .map(|alternative| parse_sequence(alternative, custom_charsets))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let count = alternatives
+                            .iter()
+                            .try_fold(0usize, |count, t| count.checked_add(t.count))
+                            .ok_or_else(|| Errors::MathError {
+                                message: String::from("算术溢出"),
+                            })?;
+                        Part::Group {
+                            alternatives,
+                            count,
+                        }
+                    }
+                }
+            }
+            '}' => {
+                return Err(Errors::invalid_template_error(
+                    "'}' without '{'".to_string(),
+                ))
+            }
+            c => {
+                literal.push(c);
+                continue;
+            }
+        };
+        if !literal.is_empty() {
+            parts.push(Part::Choices(vec![
+                std::mem::take(&mut literal).into_bytes()
+            ]));
+        }
+        parts.push(part);
+    }
+    if !literal.is_empty() {
+        parts.push(Part::Choices(vec![literal.into_bytes()]));
+    }
+    Template::new(parts)
+}
+
+/// Content of the group opened just before `chars`, consuming its closing `}`.
+fn take_group(chars: &mut std::str::Chars) -> Result<String, Errors> {
+    let mut group = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                group.push(c);
+                group.extend(chars.next());
+                continue;
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Ok(group),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        group.push(c);
+    }
+    Err(Errors::invalid_template_error(
+        "'{' without '}'".to_string(),
+    ))
+}
+
+/// Alternatives of a group, split at its commas which are not escaped or in a nested group.
+fn split_alternatives(group: &str) -> Vec<&str> {
+    let mut alternatives = vec![];
+    let mut start = 0;
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in group.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&group[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&group[start..]);
+    alternatives
+}
+
+/// Part of a `first..last` group, `None` if the group is not a range.
+fn parse_range(group: &str) -> Option<Result<Part, Errors>> {
+    let (first, last) = group.split_once("..")?;
+    let is_number = |bound: &str| !bound.is_empty() && bound.bytes().all(|b| b.is_ascii_digit());
+    if !is_number(first) || !is_number(last) {
+        return None;
+    }
+    let too_large = || Errors::invalid_template_error(format!("range {} is too large", group));
+    let (Ok(start), Ok(end)) = (first.parse::<u64>(), last.parse::<u64>()) else {
+        return Some(Err(too_large()));
+    };
+    // the count of the part must fit
+    if usize::try_from(start.abs_diff(end))
+        .ok()
+        .and_then(|c| c.checked_add(1))
+        .is_none()
+    {
+        return Some(Err(too_large()));
+    }
+    // `{01..12}` keeps the leading zeros
+    let width = if first.starts_with('0') || last.starts_with('0') {
+        first.len().max(last.len())
+    } else {
+        0
+    };
+    Some(Ok(Part::Range { start, end, width }))
+}
+
+/// Passwords matching a [`Template`], in order. Any of them can be reached in constant time
+/// so that the workers share them like the generated passwords.
+pub struct TemplateGenerator {
+    template: Arc<Template>,
+    next: usize,
+}
+impl Candidates for TemplateGenerator {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next >= self.template.count {
+            return Ok(false);
+        }
+        buffer.clear();
+        self.template.write(self.next, buffer);
+        self.next += 1;
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        self.next = self.next.saturating_add(n);
+        Ok(self.next <= self.template.count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Template;
+    use crate::candidates::{
+        test::{assert_shards_match, collect},
+        Candidates,
+    };
+
+    #[test]
+    fn alternatives() {
+        let template = Template::parse("Pa{s,$}{s,$}w0rd", &[]).unwrap();
+        assert_eq!(template.count(), 4);
+        assert_eq!(
+            collect(Box::new(template.generator())),
+            vec!["Passw0rd", "Pas$w0rd", "Pa$sw0rd", "Pa$$w0rd"]
+        );
+        let template = Template::parse("{,Mr\\,}{zhang{,san}}?1\\?", &["!.".to_string()]).unwrap();
+        assert_eq!(
+            collect(Box::new(template.generator())),
+            vec![
+                "zhang!?",
+                "zhang.?",
+                "zhangsan!?",
+                "zhangsan.?",
+                "Mr,zhang!?",
+                "Mr,zhang.?",
+                "Mr,zhangsan!?",
+                "Mr,zhangsan.?"
+            ]
+        );
+        for wrong in ["a{b", "a}b", "?x", "?2", "a\\"] {
+            assert!(
+                Template::parse(wrong, &["1".to_string()]).is_err(),
+                "{}",
+                wrong
+            );
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let template = Template::parse("zhang{1985..1995}{?d?d}!", &[]).unwrap();
+        assert_eq!(template.count(), 11 * 100);
+        let all = collect(Box::new(template.clone().generator()));
+        assert_eq!(all[0], "zhang198500!");
+        assert_eq!(all[101], "zhang198601!");
+        assert_eq!(all[1099], "zhang199599!");
+        let months = Template::parse("{01..12}{3..1}", &[]).unwrap();
+        let months = collect(Box::new(months.generator()));
+        assert_eq!(months[..4], ["013", "012", "011", "023"]);
+        assert_eq!(months.len(), 36);
+        // ranges are not stored number by number
+        let huge = Template::parse("{0..9999999999}-{9..0}", &[]).unwrap();
+        assert_eq!(huge.count(), 100_000_000_000);
+        let mut generator = huge.generator();
+        assert!(generator.advance_by(1_234_567_893).unwrap());
+        let mut buffer = Vec::new();
+        assert!(generator.next_into(&mut buffer).unwrap());
+        assert_eq!(buffer, b"0123456789-6");
+        assert!(Template::parse("{0..99999999999999999999}", &[]).is_err());
+        assert_shards_match(|| Box::new(template.clone().generator()), 3);
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use crate::{
+    candidates::Candidates,
+    errors::Errors,
+    password_reader::{LineBatch, SeenLines},
+};
+
+/// Keys of a QWERTY keyboard, row by row, without and with shift.
+const KEYBOARD: [[&str; 4]; 2] = [
+    ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"],
+    ["!@#$%^&*()_+", "QWERTYUIOP{}", "ASDFGHJKL:\"", "ZXCVBNM<>?"],
+];
+/// Upper bound of the estimated number of variants, which are all kept in memory.
+const MAX_VARIANTS: u64 = 20_000_000;
+
+/// Variants of a remembered password which does not work, within `max_edits` typos.
+///
+/// A typo is an insertion, a deletion or a substitution of a character of `charset`,
+/// a swap of two adjacent characters, a substitution by a neighbouring key of a QWERTY
+/// keyboard, or the case of every letter inverted by caps lock. The variants are ordered
+/// by their number of typos, the password itself first, and each is tested once. They are
+/// all kept in memory, so too many typos for a long password or a large charset are rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Typos {
+    password: Vec<char>,
+    max_edits: usize,
+    charset: Vec<char>,
+}
+impl Typos {
+    pub fn new(password: &str, max_edits: usize, charset: &[char]) -> Result<Self, Errors> {
+        let typos = Typos {
+            password: password.chars().collect(),
+            max_edits,
+            charset: charset.to_vec(),
+        };
+        if typos.estimate() > MAX_VARIANTS {
+            return Err(Errors::invalid_typos_error(format!(
+                "{} typos of a password of {} characters may give more than {} variants, \
+                 which are all kept in memory",
+                max_edits,
+                typos.password.len(),
+                MAX_VARIANTS
+            )));
+        }
+        Ok(typos)
+    }
+    /// Number of distinct variants, which are all generated to be counted.
+    pub fn count(&self) -> usize {
+        self.variants().len()
+    }
+    /// Every variant, one typo more than the previous ones at a time.
+    pub(crate) fn variants(&self) -> LineBatch {
+        let mut seen = SeenLines::default();
+        let mut variants = LineBatch::default();
+        let mut buffer = String::new();
+        let mut emit = |word: &[char], variants: &mut LineBatch| {
+            buffer.clear();
+            buffer.extend(word);
+            if seen.insert(buffer.as_bytes()) {
+                variants.push(buffer.as_bytes());
+            }
+        };
+        emit(&self.password, &mut variants);
+        let mut level_start = 0;
+        for _ in 0..self.max_edits {
+            let level_end = variants.len();
+            if level_start == level_end {
+                // no new variant with the last typo
+                break;
+            }
+            for i in level_start..level_end {
+                let word: Vec<char> = std::str::from_utf8(variants.line(i))
+                    .expect("variants are built from chars")
+                    .chars()
+                    .collect();
+                self.edit(&word, |edited| emit(edited, &mut variants));
+            }
+            level_start = level_end;
+        }
+        variants
+    }
+
+    /// Upper bound of the number of variants, computed until it exceeds [`MAX_VARIANTS`].
+    fn estimate(&self) -> u64 {
+        let charset = self.charset.len() as u64;
+        let mut total: u64 = 1;
+        let mut level: u64 = 1;
+        for edit in 0..self.max_edits {
+            // the longest words of the level, after one insertion per typo
+            let insertions = if charset > 0 { edit } else { 0 };
+            let len = (self.password.len() + insertions) as u64;
+            // swaps, deletions, up to 6 keyboard neighbours and the charset per character,
+            // insertions
+            let edits = len.saturating_sub(1) + len + len * (6 + charset) + (len + 1) * charset;
+            if edits == 0 || total > MAX_VARIANTS {
+                break;
+            }
+            level = level.saturating_mul(edits);
+            total = total.saturating_add(level);
+        }
+        // caps lock at most doubles them
+        total.saturating_mul(2)
+    }
+
+    /// Calls `emit` with every variant of `word` with one more typo, the likeliest first.
+    fn edit(&self, word: &[char], mut emit: impl FnMut(&[char])) {
+        let mut edited = word.to_vec();
+        if word.iter().any(char::is_ascii_alphabetic) {
+            for c in edited.iter_mut() {
+                *c = if c.is_ascii_lowercase() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                };
+            }
+            emit(&edited);
+        }
+        for i in 1..word.len() {
+            if word[i - 1] != word[i] {
+                edited.clone_from_slice(word);
+                edited.swap(i - 1, i);
+                emit(&edited);
+            }
+        }
+        for i in 0..word.len() {
+            edited.clear();
+            edited.extend_from_slice(&word[..i]);
+            edited.extend_from_slice(&word[i + 1..]);
+            emit(&edited);
+        }
+        edited.clear();
+        edited.extend_from_slice(word);
+        for i in 0..word.len() {
+            for neighbour in keyboard_neighbours(word[i]) {
+                edited[i] = neighbour;
+                emit(&edited);
+            }
+            for &c in &self.charset {
+                if c != word[i] {
+                    edited[i] = c;
+                    emit(&edited);
+                }
+            }
+            edited[i] = word[i];
+        }
+        for i in 0..=word.len() {
+            edited.insert(i, ' ');
+            for &c in &self.charset {
+                edited[i] = c;
+                emit(&edited);
+            }
+            edited.remove(i);
+        }
+    }
+}
+
+/// Keys around `key` on a QWERTY keyboard, with the same shift state.
+fn keyboard_neighbours(key: char) -> Vec<char> {
+    for layer in &KEYBOARD {
+        for (row, keys) in layer.iter().enumerate() {
+            if let Some(column) = keys.chars().position(|k| k == key) {
+                let (row, column) = (row as isize, column as isize);
+                // the rows are shifted by half a key to the right from top to bottom
+                let around = [
+                    (row - 1, column),
+                    (row - 1, column + 1),
+                    (row, column - 1),
+                    (row, column + 1),
+                    (row + 1, column - 1),
+                    (row + 1, column),
+                ];
+                return around
+                    .iter()
+                    .filter(|(row, column)| *row >= 0 && *column >= 0)
+                    .filter_map(|&(row, column)| {
+                        layer.get(row as usize)?.chars().nth(column as usize)
+                    })
+                    .collect();
+            }
+        }
+    }
+    vec![]
+}
+
+/// Candidates of [`Typos`], generated once and shared by all workers.
+pub struct TypoCandidates {
+    variants: Arc<LineBatch>,
+    next: usize,
+}
+impl TypoCandidates {
+    pub(crate) fn new(variants: Arc<LineBatch>) -> Self {
+        TypoCandidates { variants, next: 0 }
+    }
+}
+impl Candidates for TypoCandidates {
+    fn next_into(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Errors> {
+        if self.next >= self.variants.len() {
+            return Ok(false);
+        }
+        buffer.clear();
+        buffer.extend_from_slice(self.variants.line(self.next));
+        self.next += 1;
+        Ok(true)
+    }
+
+    fn advance_by(&mut self, n: usize) -> Result<bool, Errors> {
+        self.next = self.next.saturating_add(n);
+        Ok(self.next <= self.variants.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{keyboard_neighbours, TypoCandidates, Typos};
+    use crate::candidates::test::{assert_shards_match, collect};
+
+    #[test]
+    fn neighbours() {
+        assert_eq!(keyboard_neighbours('s'), vec!['w', 'e', 'a', 'd', 'z', 'x']);
+        assert_eq!(keyboard_neighbours('Q'), vec!['!', '@', 'W', 'A']);
+        assert!(keyboard_neighbours('中').is_empty());
+    }
+
+    #[test]
+    fn one_typo() {
+        let typos = Typos::new("ab", 1, &['1']).unwrap();
+        let all = collect(Box::new(TypoCandidates::new(Arc::new(typos.variants()))));
+        #[rustfmt::skip]
+        assert_eq!(
+            all,
+            vec![
+                "ab",
+                // caps lock, swap, deletions
+                "AB", "ba", "b", "a",
+                // keyboard neighbours and charset for 'a'
+                "qb", "wb", "sb", "zb", "1b",
+                // for 'b'
+                "ag", "ah", "av", "an", "a1",
+                // insertions
+                "1ab", "a1b", "ab1",
+            ]
+        );
+        assert_eq!(typos.count(), all.len());
+    }
+
+    #[test]
+    fn fewest_typos_first() {
+        let typos = Typos::new("pass", 2, &['1', 's']).unwrap();
+        let variants = Arc::new(typos.variants());
+        let all = collect(Box::new(TypoCandidates::new(variants.clone())));
+        let position = |variant: &str| all.iter().position(|v| v == variant).unwrap();
+        let one_typo = Typos::new("pass", 1, &['1', 's']).unwrap().count();
+        assert!(position("pas") < one_typo);
+        assert!(position("PAS") >= one_typo);
+        assert!(position("aps1") >= one_typo);
+        // every variant is tested once
+        let mut distinct = all.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), all.len());
+        assert_shards_match(|| Box::new(TypoCandidates::new(variants.clone())), 3);
+    }
+
+    #[test]
+    fn too_many_variants() {
+        let charset: Vec<char> = (' '..='~').collect();
+        assert!(Typos::new("password123", 2, &charset).is_ok());
+        assert!(Typos::new("password123", 3, &charset).is_err());
+        assert!(Typos::new("a", usize::MAX, &[]).is_err());
+        // nothing to edit
+        assert_eq!(Typos::new("", usize::MAX, &[]).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::Path;
+
+use crate::errors::Errors;
+
+/// A password check for one file format.
+///
+/// A verifier is prepared once per target file and worker, then asked to test
+/// candidates one by one. Implement it to support a format from outside this crate
+/// and register it with [`VerifierRegistry::register`].
+pub trait Verifier: Send {
+    /// Opens and validates the target file, keeping whatever state is needed to test passwords quickly.
+    fn prepare(file_path: &Path) -> Result<Self, Errors>
+    where
+        Self: Sized;
+
+    /// Returns `true` if `password` unlocks the target.
+    /// An error stops the whole search and is returned by [`crate::Cracker::start`].
+    fn verify(&mut self, password: &[u8]) -> Result<bool, Errors>;
+
+    /// Human readable information about the target, shown once before the search starts.
+    fn describe(&self) -> Option<String> {
+        None
+    }
+
+    /// Number of candidates each worker tests between two checks of the stop signal.
+    /// Slow formats should use a smaller value to stay responsive.
+    fn batch_size(&self) -> usize {
+        500
+    }
+
+    /// Whether `verify` tests the bytes of the password as they are. Formats which turn them
+    /// back into a string, losing the CP437 and CP936 encodings, return `false`.
+    fn takes_raw_bytes(&self) -> bool {
+        true
+    }
+}
+
+type PrepareFn = fn(&Path) -> Result<Box<dyn Verifier>, Errors>;
+
+fn prepare_boxed<V: Verifier + 'static>(file_path: &Path) -> Result<Box<dyn Verifier>, Errors> {
+    Ok(Box::new(V::prepare(file_path)?))
+}
+
+/// Maps MIME types, as detected by `infer`, to the [`Verifier`] handling them.
+#[derive(Clone)]
+pub struct VerifierRegistry {
+    verifiers: Vec<(String, PrepareFn)>,
+}
+impl VerifierRegistry {
+    /// A registry without any format, not even the built-in ones.
+    pub fn empty() -> Self {
+        VerifierRegistry { verifiers: vec![] }
+    }
+    /// Registers `V` for `mime_type`, replacing any verifier registered before for it.
+    pub fn register<V: Verifier + 'static>(&mut self, mime_type: &str) -> &mut Self {
+        self.verifiers.retain(|(m, _)| m != mime_type);
+        self.verifiers
+            .push((mime_type.to_string(), prepare_boxed::<V> as PrepareFn));
+        self
+    }
+    pub fn supports(&self, mime_type: &str) -> bool {
+        self.verifiers.iter().any(|(m, _)| m == mime_type)
+    }
+    /// Detects the format of `file_path` and returns the MIME type of the verifier handling it.
+    pub fn detect(&self, file_path: &Path) -> Result<String, Errors> {
+        match infer::get_from_path(file_path)? {
+            Some(file_type) if self.supports(file_type.mime_type()) => {
+                Ok(file_type.mime_type().to_string())
+            }
+            Some(file_type) => Err(Errors::UnsupportedFormat {
+                file_type: file_type.mime_type().to_string(),
+                feature: required_feature(file_type.mime_type()),
+            }),
+            None => Err(Errors::UnsupportedFormat {
+                file_type: "unknown".to_string(),
+                feature: None,
+            }),
+        }
+    }
+    /// Prepares the verifier registered for `mime_type`, if any.
+    pub fn prepare(
+        &self,
+        mime_type: &str,
+        file_path: &Path,
+    ) -> Option<Result<Box<dyn Verifier>, Errors>> {
+        self.verifiers
+            .iter()
+            .find(|(m, _)| m == mime_type)
+            .map(|(_, prepare)| prepare(file_path))
+    }
+}
+impl Default for VerifierRegistry {
+    /// A registry with every format enabled through cargo features.
+    fn default() -> Self {
+        let mut registry = VerifierRegistry::empty();
+        registry.register::<crate::zip::ZipVerifier>("application/zip");
+        #[cfg(feature = "rar")]
+        registry.register::<crate::rar::RarVerifier>("application/vnd.rar");
+        #[cfg(feature = "7z")]
+        registry.register::<crate::seven_z::SevenZVerifier>("application/x-7z-compressed");
+        #[cfg(feature = "pdf")]
+        registry.register::<crate::pdf::PdfVerifier>("application/pdf");
+        registry
+    }
+}
+
+/// Cargo feature providing the built-in verifier for `mime_type`.
+fn required_feature(mime_type: &str) -> Option<&'static str> {
+    match mime_type {
+        "application/vnd.rar" => Some("rar"),
+        "application/x-7z-compressed" => Some("7z"),
+        "application/pdf" => Some("pdf"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{Verifier, VerifierRegistry};
+    use crate::errors::Errors;
+
+    struct Fixed;
+    impl Verifier for Fixed {
+        fn prepare(_file_path: &Path) -> Result<Self, Errors> {
+            Ok(Fixed)
+        }
+        fn verify(&mut self, password: &[u8]) -> Result<bool, Errors> {
+            Ok(password == b"secret")
+        }
+    }
+
+    #[test]
+    fn register_custom_verifier() {
+        let mut registry = VerifierRegistry::empty();
+        assert!(!registry.supports("application/x-custom"));
+        registry.register::<Fixed>("application/x-custom");
+        assert!(registry.supports("application/x-custom"));
+
+        let mut verifier = registry
+            .prepare("application/x-custom", Path::new("unused"))
+            .unwrap()
+            .unwrap();
+        assert!(verifier.verify(b"secret").unwrap());
+        assert!(!verifier.verify(b"other").unwrap());
+        assert!(registry.prepare("application/zip", Path::new("unused")).is_none());
+    }
+
+    #[test]
+    fn default_registry_has_zip() {
+        assert!(VerifierRegistry::default().supports("application/zip"));
+    }
+
+    #[test]
+    fn detect_unsupported_format() {
+        let registry = VerifierRegistry::empty();
+        let path = std::env::temp_dir().join("password_crack_detect.pdf");
+        std::fs::write(&path, b"%PDF-1.7\n").unwrap();
+        match registry.detect(&path) {
+            Err(Errors::UnsupportedFormat { file_type, feature }) => {
+                assert_eq!(file_type, "application/pdf");
+                assert_eq!(feature, Some("pdf"));
+            }
+            _ => panic!("pdf should not be supported by an empty registry"),
+        }
+        std::fs::write(&path, b"plain text").unwrap();
+        assert!(matches!(
+            registry.detect(&path),
+            Err(Errors::UnsupportedFormat { feature: None, .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
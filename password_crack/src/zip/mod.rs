@@ -1,3 +1,3 @@
 mod zip;
 pub mod zip_utils;
-pub use self::zip::password_check;
+pub use self::zip::ZipVerifier;
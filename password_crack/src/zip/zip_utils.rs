@@ -27,18 +27,15 @@ impl AesInfo {
 }
 
 // validate that the zip requires a password
-pub fn validate_zip(file_path: &Path, show_info: bool) -> Result<Option<AesInfo>, Errors> {
+pub fn validate_zip(file_path: &Path) -> Result<Option<AesInfo>, Errors> {
     let file = File::open(file_path)?;
     let mut archive = zip::ZipArchive::new(file)?;
     let aes_data = archive.get_aes_key_and_salt(0);
     let zip_result = archive.by_index(0);
     let aes_info = match zip_result {
-        Ok(_) => Err(Errors::invalid_zip_error(
-            "the archive is not encrypted".to_string(),
-        )),
+        Ok(_) => Err(Errors::NotEncrypted),
         Err(UnsupportedArchive(msg)) if msg == "Password required to decrypt file" => {
-            if let Some((aes_mode, key_, salt_)) = aes_data.expect("Archive validated before-hand")
-            {
+            if let Some((aes_mode, key_, salt_)) = aes_data? {
                 let aes_key_length = aes_mode.key_length();
                 let key = key_;
                 let salt = salt_;
@@ -49,17 +46,6 @@ pub fn validate_zip(file_path: &Path, show_info: bool) -> Result<Option<AesInfo>
         }
         Err(e) => Err(Errors::invalid_zip_error(format!("Unexpected error {e:?}"))),
     }?;
-    if show_info {
-        match &aes_info {
-            Some(aes_info) => println!(
-                "Archive is encrypted with AES{} - expect a long wait time",
-                aes_info.aes_key_length * 8
-            ),
-            None => {
-                println!("Archive is encrypted with ZipCrypto - expect a much faster throughput")
-            }
-        }
-    }
 
     Ok(aes_info)
 }